@@ -0,0 +1,261 @@
+use crate::{Md2PdfError, Result};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// How deep included files may include others, to stop include cycles
+const MAX_DEPTH: usize = 10;
+
+/// Markdown with its include links expanded
+#[derive(Debug)]
+pub struct Expanded {
+    pub markdown: String,
+    /// The files included, for watching them
+    pub files: Vec<PathBuf>,
+}
+
+/// Expand mdBook's `{{#include}}`, `{{#rustdoc_include}}` and
+/// `{{#playground}}` links, with paths relative to `dir`
+///
+/// A path may be followed by `:name` for the lines between `ANCHOR: name`
+/// and `ANCHOR_END: name`, or by a line range such as `:5`, `:2:10`, `:3:`
+/// or `::10`. `\{{#include ...}}` is left for the variable pass, which turns
+/// it into literal text. `{{#title ...}}` is removed, as the book title comes
+/// from `book.toml`. Included files must be inside `root`.
+pub fn expand(markdown: &str, dir: &Path, root: &Path) -> Result<Expanded> {
+    // A manifest in the current directory has an empty root
+    let root = if root.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        root
+    };
+    let root = root
+        .canonicalize()
+        .map_err(|e| Md2PdfError::InvalidInput(format!("cannot read {}: {e}", root.display())))?;
+    let mut files = Vec::new();
+    let markdown = expand_at(markdown, dir, &root, 0, &mut files)?;
+    Ok(Expanded { markdown, files })
+}
+
+fn expand_at(
+    markdown: &str,
+    dir: &Path,
+    root: &Path,
+    depth: usize,
+    files: &mut Vec<PathBuf>,
+) -> Result<String> {
+    let mut output = String::with_capacity(markdown.len());
+    let mut rest = markdown;
+
+    while let Some(start) = rest.find("{{#") {
+        let escaped = rest[..start].ends_with('\\');
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        let link = &rest[start + 3..start + len];
+        let end = start + len + 2;
+
+        let mut words = link.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let argument = words.next().unwrap_or_default();
+        let replacement = match command {
+            _ if escaped => None,
+            "include" | "rustdoc_include" | "playground" | "playpen" => {
+                if depth >= MAX_DEPTH {
+                    return Err(Md2PdfError::InvalidInput(format!(
+                        "`{{{{#{link}}}}}` is nested more than {MAX_DEPTH} includes deep"
+                    )));
+                }
+                Some(include(argument, dir, root, depth, files)?)
+            }
+            "title" => Some(String::new()),
+            _ => None,
+        };
+
+        match replacement {
+            Some(text) => {
+                output.push_str(&rest[..start]);
+                output.push_str(&text);
+            }
+            None => output.push_str(&rest[..end]),
+        }
+        rest = &rest[end..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// The text for one include link, itself expanded
+fn include(
+    argument: &str,
+    dir: &Path,
+    root: &Path,
+    depth: usize,
+    files: &mut Vec<PathBuf>,
+) -> Result<String> {
+    let (file, selector) = argument.split_once(':').unwrap_or((argument, ""));
+    let path = dir.join(file);
+    let unreadable = |e: std::io::Error| {
+        Md2PdfError::InvalidInput(format!("cannot include {}: {e}", path.display()))
+    };
+    let path = path.canonicalize().map_err(unreadable)?;
+    if !path.starts_with(root) {
+        return Err(Md2PdfError::InvalidInput(format!(
+            "cannot include `{file}`: it is outside the book directory {}",
+            root.display()
+        )));
+    }
+    let text = fs::read_to_string(&path).map_err(unreadable)?;
+    if !files.contains(&path) {
+        files.push(path.clone());
+    }
+
+    let selected = match line_range(selector) {
+        Some(range) => lines(&text, range),
+        None => anchored(&text, selector).ok_or_else(|| {
+            Md2PdfError::InvalidInput(format!(
+                "anchor `{selector}` not found in {}",
+                path.display()
+            ))
+        })?,
+    };
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    expand_at(selected.trim_end_matches('\n'), dir, root, depth + 1, files)
+}
+
+/// Parse `""`, `5`, `2:10`, `3:` or `:10` into a range of 0-based line indices
+fn line_range(selector: &str) -> Option<Range<usize>> {
+    let bound = |s: &str| -> Option<Option<usize>> {
+        if s.is_empty() {
+            Some(None)
+        } else {
+            s.parse().ok().map(Some)
+        }
+    };
+
+    match selector.split_once(':') {
+        None if selector.is_empty() => Some(0..usize::MAX),
+        None => {
+            let line: usize = selector.parse().ok()?;
+            Some(line.saturating_sub(1)..line)
+        }
+        Some((start, end)) => {
+            let start = bound(start)?.unwrap_or(1).saturating_sub(1);
+            let end = bound(end)?.unwrap_or(usize::MAX);
+            Some(start..end)
+        }
+    }
+}
+
+fn lines(text: &str, range: Range<usize>) -> String {
+    text.split_inclusive('\n')
+        .skip(range.start)
+        .take(range.end.saturating_sub(range.start))
+        .collect()
+}
+
+/// The lines between `ANCHOR: name` and `ANCHOR_END: name`, without the
+/// marker lines of any anchor
+fn anchored(text: &str, name: &str) -> Option<String> {
+    let mut selected = String::new();
+    let mut inside = false;
+    let mut found = false;
+
+    for line in text.split_inclusive('\n') {
+        if let Some(anchor) = marker(line, "ANCHOR_END:") {
+            if anchor == name {
+                inside = false;
+            }
+        } else if let Some(anchor) = marker(line, "ANCHOR:") {
+            if anchor == name {
+                inside = true;
+                found = true;
+            }
+        } else if inside {
+            selected.push_str(line);
+        }
+    }
+
+    found.then_some(selected)
+}
+
+fn marker<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    let (_, rest) = line.split_once(prefix)?;
+    rest.trim_start()
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+        .next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "use std::io;\n// ANCHOR: main\nfn main() {\n    // ANCHOR: body\n    run();\n    // ANCHOR_END: body\n}\n// ANCHOR_END: main\n";
+
+    #[test]
+    fn test_include_anchor_and_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), SOURCE).unwrap();
+
+        let expanded = expand(
+            "```rust\n{{#include main.rs:main}}\n```\n",
+            dir.path(),
+            dir.path(),
+        )
+        .unwrap();
+        assert_eq!(
+            expanded.markdown,
+            "```rust\nfn main() {\n    run();\n}\n```\n"
+        );
+        let main = dir.path().join("main.rs").canonicalize().unwrap();
+        assert_eq!(expanded.files, [main]);
+
+        let expanded = expand("{{#rustdoc_include main.rs:body}}", dir.path(), dir.path());
+        assert_eq!(expanded.unwrap().markdown, "    run();");
+
+        let expanded = expand(
+            "{{#include main.rs:1}} and {{#include main.rs::1}}",
+            dir.path(),
+            dir.path(),
+        );
+        assert_eq!(expanded.unwrap().markdown, "use std::io; and use std::io;");
+
+        let expanded = expand("{{#include main.rs:7:}}", dir.path(), dir.path());
+        assert_eq!(expanded.unwrap().markdown, "}\n// ANCHOR_END: main");
+    }
+
+    #[test]
+    fn test_escaped_and_unknown_links_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let text = "\\{{#include main.rs}} {{#other}} {{ version }}";
+        let expanded = expand(text, dir.path(), dir.path()).unwrap();
+        assert_eq!(expanded.markdown, text);
+        assert!(expanded.files.is_empty());
+    }
+
+    #[test]
+    fn test_includes_stay_in_the_root() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("book")).unwrap();
+        fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+        fs::write(dir.path().join("book/main.rs"), SOURCE).unwrap();
+        let root = dir.path().join("book");
+
+        let expanded = expand("{{#include main.rs:1}}", &root, &root).unwrap();
+        assert_eq!(expanded.markdown, "use std::io;");
+        let err = expand("{{#include ../secret.txt}}", &root, &root).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("cannot include `../secret.txt`: it is outside"));
+    }
+
+    #[test]
+    fn test_missing_anchor_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), SOURCE).unwrap();
+        let err = expand("{{#include main.rs:nope}}", dir.path(), dir.path()).unwrap_err();
+        assert!(err.to_string().contains("anchor `nope`"));
+    }
+}
@@ -0,0 +1,119 @@
+use super::{summary, Book};
+use crate::{Md2PdfError, Result};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The parts of mdBook's `book.toml` that affect the PDF
+#[derive(Debug, Default, Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    book: BookSection,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct BookSection {
+    title: Option<String>,
+    authors: Vec<String>,
+    description: Option<String>,
+    language: Option<String>,
+    src: PathBuf,
+}
+
+impl Default for BookSection {
+    fn default() -> Self {
+        Self {
+            title: None,
+            authors: Vec::new(),
+            description: None,
+            language: None,
+            src: PathBuf::from("src"),
+        }
+    }
+}
+
+/// Read an mdBook project from its `book.toml`
+///
+/// Chapters come from `SUMMARY.md` in the source directory, and the title,
+/// authors, description and language become book metadata.
+pub fn load(manifest: &Path) -> Result<Book> {
+    let project: Manifest = toml::from_str(&fs::read_to_string(manifest)?)?;
+    let book = project.book;
+    let project = manifest.parent().unwrap_or(Path::new(""));
+    let root = project.join(&book.src);
+
+    let summary_path = root.join("SUMMARY.md");
+    let summary = fs::read_to_string(&summary_path).map_err(|e| {
+        Md2PdfError::InvalidInput(format!("cannot read {}: {e}", summary_path.display()))
+    })?;
+
+    let mut metadata = Mapping::new();
+    let mut insert = |key: &str, value: Option<String>| {
+        if let Some(value) = value {
+            metadata.insert(Value::from(key), Value::from(value));
+        }
+    };
+    insert("title", book.title);
+    insert(
+        "author",
+        (!book.authors.is_empty()).then(|| book.authors.join(", ")),
+    );
+    insert("description", book.description);
+    insert("lang", book.language);
+
+    Ok(Book {
+        metadata,
+        root,
+        include_root: project.to_path_buf(),
+        chapters: summary::parse(&summary),
+        numbering: Some("1.1".to_string()),
+        outline: true,
+        summary: Some(summary_path),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_load_mdbook() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(
+            dir.path().join("book.toml"),
+            "[book]\ntitle = \"The Guide\"\nauthors = [\"Ana\", \"Bo\"]\nlanguage = \"en\"\n\n[output.html]\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("src/SUMMARY.md"),
+            "# Summary\n\n[Preface](preface.md)\n\n# Basics\n\n- [Start](start.md)\n- [Later]()\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/preface.md"), "# Preface\n").unwrap();
+        fs::write(
+            dir.path().join("src/start.md"),
+            "# Start\n\n```rust\n{{#include start.rs}}\n```\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("src/start.rs"), "fn main() {}\n").unwrap();
+
+        let book = Book::load(&dir.path().join("book.toml")).unwrap();
+        assert_eq!(book.metadata.get("author"), Some(&Value::from("Ana, Bo")));
+
+        let typst = book.to_typst(&Config::default()).unwrap().output;
+        let preface = typst.find("= Preface").unwrap();
+        let part = typst
+            .find("heading(level: 1, numbering: none)[Basics]")
+            .unwrap();
+        let numbering_off = typst.find("#set heading(numbering: none)").unwrap();
+        let numbering_on = typst.rfind("#set heading(numbering: \"1.1\")").unwrap();
+        assert!(numbering_off < preface && preface < numbering_on);
+        assert!(part < numbering_on);
+        assert!(typst.contains("fn main() {}"));
+        assert!(typst.contains("= Later"));
+    }
+}
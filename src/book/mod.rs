@@ -0,0 +1,440 @@
+pub mod include;
+pub mod mdbook;
+pub mod summary;
+
+use crate::config::Config;
+use crate::parser::frontmatter::{Frontmatter, MetadataFormat};
+use crate::parser::{markdown::parse_markdown, variables::substitute};
+use crate::renderer::pdf::Attachment;
+use crate::transpiler::typst::{
+    book_preamble, chapter_to_typst, normalize, numbering_rule, part_heading, section_heading,
+    ChapterContext,
+};
+use crate::{Converted, Md2PdfError, Result};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Several Markdown files combined into one document
+#[derive(Debug, Clone, Default)]
+pub struct Book {
+    /// Title, author and other metadata for the title page and variables
+    pub metadata: Mapping,
+    /// Directory that chapter paths are relative to
+    pub root: PathBuf,
+    /// Directory that `{{#include}}` files must be in: the project for an
+    /// mdBook, otherwise `root`
+    pub include_root: PathBuf,
+    /// Chapters in reading order, nested ones flattened with their depth
+    pub chapters: Vec<Chapter>,
+    /// Heading numbering pattern; `None` leaves headings unnumbered
+    pub numbering: Option<String>,
+    /// Whether to include a table of contents after the title page
+    pub outline: bool,
+    /// The `SUMMARY.md` listing the chapters, when it is not the manifest
+    pub summary: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub title: String,
+    /// File relative to the book root; `None` for a heading without content
+    pub path: Option<PathBuf>,
+    /// Nesting level, 0 for top-level chapters
+    pub depth: usize,
+    pub kind: ChapterKind,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChapterKind {
+    /// A regular chapter, numbered when the book has numbering
+    #[default]
+    Numbered,
+    /// An mdBook prefix or suffix chapter, never numbered
+    Unnumbered,
+    /// A part title, on a page of its own
+    Part,
+}
+
+/// An entry in the `chapters` list of `book.yaml`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    File(PathBuf),
+    Section {
+        file: Option<PathBuf>,
+        title: Option<String>,
+        #[serde(default)]
+        chapters: Vec<Entry>,
+    },
+}
+
+/// Whether `path` names a book manifest rather than a Markdown file
+///
+/// A `SUMMARY.md` only counts next to a `book.toml`; elsewhere it is an
+/// ordinary document unless `Config::book` says otherwise.
+pub fn is_manifest(path: &Path) -> bool {
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some("book.toml" | "book.yaml" | "book.yml" | "book.json") => true,
+        Some("SUMMARY.md") => path.with_file_name("book.toml").is_file(),
+        _ => false,
+    }
+}
+
+impl Book {
+    /// Read a `book.yaml` (or `.json`) manifest, or an mdBook `book.toml`
+    /// or `SUMMARY.md`
+    pub fn load(manifest: &Path) -> Result<Self> {
+        if manifest
+            .file_name()
+            .is_some_and(|name| name.eq_ignore_ascii_case("book.toml"))
+        {
+            return mdbook::load(manifest);
+        }
+
+        let root = manifest.parent().map(Path::to_path_buf).unwrap_or_default();
+        let text = fs::read_to_string(manifest)?;
+
+        let is_summary = manifest
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("md"));
+        if is_summary {
+            return Ok(Self {
+                include_root: root.clone(),
+                root,
+                chapters: summary::parse(&text),
+                numbering: Some("1.1".to_string()),
+                outline: true,
+                ..Default::default()
+            });
+        }
+
+        let mut metadata = match MetadataFormat::from_path(manifest).parse(&text)? {
+            Value::Mapping(mapping) => mapping,
+            _ => {
+                return Err(Md2PdfError::InvalidInput(format!(
+                    "{}: a book manifest must be a mapping with a `chapters` list",
+                    manifest.display()
+                )))
+            }
+        };
+        let entries: Vec<Entry> = metadata
+            .remove("chapters")
+            .map(serde_yaml::from_value)
+            .transpose()?
+            .unwrap_or_default();
+        let numbering = match metadata.remove("numbering") {
+            None => Some("1.1".to_string()),
+            Some(Value::String(pattern)) if pattern != "none" => Some(pattern),
+            Some(_) => None,
+        };
+        let outline = match metadata.remove("outline") {
+            Some(Value::Bool(outline)) => outline,
+            _ => true,
+        };
+
+        let mut chapters = Vec::new();
+        flatten(&entries, 0, &mut chapters);
+        if chapters.is_empty() {
+            return Err(Md2PdfError::InvalidInput(format!(
+                "{}: the book has no chapters",
+                manifest.display()
+            )));
+        }
+
+        Ok(Self {
+            metadata,
+            include_root: root.clone(),
+            root,
+            chapters,
+            numbering,
+            outline,
+            summary: None,
+        })
+    }
+
+    /// Chapter files, resolved against the book root, and the summary
+    pub fn files(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.chapters
+            .iter()
+            .filter_map(|c| c.path.as_ref())
+            .map(|path| self.root.join(path))
+            .chain(self.summary.clone())
+    }
+
+    /// Combine the chapters into one Typst document
+    ///
+    /// Metadata from `config` overrides the book's, which overrides each
+    /// chapter's own frontmatter.
+    pub fn to_typst(&self, config: &Config) -> Result<Converted<String>> {
+        self.transpile(config).map(|(converted, _)| converted)
+    }
+
+    /// [`Book::to_typst`], and the files the chapters included, for watching
+    pub(crate) fn transpile(&self, config: &Config) -> Result<(Converted<String>, Vec<PathBuf>)> {
+        let metadata = self.frontmatter(config)?;
+        let today = crate::utils::date::today(config.reproducible);
+        let config = &config.with_document_style(metadata.theme.as_deref(), &metadata.style)?;
+        let overrides = merged(&self.metadata, &config.metadata);
+
+        let labels: HashMap<PathBuf, String> = self
+            .chapters
+            .iter()
+            .filter_map(|c| c.path.as_ref())
+            .map(|path| (normalize(path), chapter_label(path)))
+            .collect();
+
+        let mut output = book_preamble(&metadata, self.numbering.as_deref(), self.outline, config)?;
+
+        let mut warnings = Vec::new();
+        let mut includes = Vec::new();
+        let mut numbered = true;
+        for chapter in &self.chapters {
+            if chapter.depth == 0 {
+                output.push_str("\n#pagebreak(weak: true)\n");
+            }
+
+            if let Some(numbering) = &self.numbering {
+                let numbers = chapter.kind == ChapterKind::Numbered;
+                if numbers != numbered {
+                    output.push_str(&numbering_rule(numbers.then_some(numbering.as_str())));
+                    numbered = numbers;
+                }
+            }
+
+            if chapter.kind == ChapterKind::Part {
+                output.push_str(&part_heading(&chapter.title));
+                continue;
+            }
+
+            let Some(path) = &chapter.path else {
+                output.push_str(&section_heading(&chapter.title, chapter.depth));
+                continue;
+            };
+
+            let context = ChapterContext {
+                heading_offset: chapter.depth,
+                label: labels[&normalize(path)].clone(),
+                dir: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+                chapters: &labels,
+            };
+            let body = self
+                .chapter_body(path, &overrides, today, &context, config, &mut includes)
+                .map_err(|e| Md2PdfError::Chapter {
+                    path: path.clone(),
+                    source: Box::new(e),
+                })?;
+            output.push_str(&body.output);
+            // Each chapter's warnings name its file
+            for warning in body.warnings {
+                warnings.push(format!("{}: {warning}", path.display()));
+            }
+        }
+
+        Ok((Converted { output, warnings }, includes))
+    }
+
+    /// `config` with the theme and style set in the book's metadata
+    pub fn styled_config(&self, config: &Config) -> Result<Config> {
+        let metadata = self.frontmatter(config)?;
+        config.with_document_style(metadata.theme.as_deref(), &metadata.style)
+    }
+
+    /// The book's metadata, with `config.metadata` merged over it
+    fn frontmatter(&self, config: &Config) -> Result<Frontmatter> {
+        let mut metadata = Frontmatter::default();
+        metadata.merge(&self.metadata)?;
+        metadata.merge(&config.metadata)?;
+        metadata.resolve_date(crate::utils::date::today(config.reproducible))?;
+        Ok(metadata)
+    }
+
+    fn chapter_body(
+        &self,
+        path: &Path,
+        overrides: &Mapping,
+        today: Option<chrono::NaiveDate>,
+        context: &ChapterContext<'_>,
+        config: &Config,
+        includes: &mut Vec<PathBuf>,
+    ) -> Result<Converted<String>> {
+        let file = self.root.join(path);
+        let markdown = fs::read_to_string(&file)?;
+        let dir = file.parent().unwrap_or(Path::new(""));
+        let expanded = include::expand(&markdown, dir, &self.include_root)?;
+        includes.extend(expanded.files);
+        let markdown = expanded.markdown;
+        let (mut frontmatter, content) = Frontmatter::extract(&markdown)?;
+        frontmatter.merge(overrides)?;
+        frontmatter.resolve_date(today)?;
+        let first_line = markdown[..markdown.len() - content.len()]
+            .matches('\n')
+            .count()
+            + 1;
+        let content = substitute(content, &frontmatter.variables(), first_line)?;
+        Ok(chapter_to_typst(parse_markdown(&content), context, config))
+    }
+
+    /// The manifest and chapter files, to embed with `embed_source`
+    pub fn attachments(&self, manifest: &Path) -> Result<Vec<Attachment>> {
+        let mut attachments = vec![Attachment {
+            name: manifest
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            data: fs::read(manifest)?,
+            mime_type: crate::utils::images::mime_type(&manifest.to_string_lossy()).to_string(),
+            description: "Book manifest".to_string(),
+            relationship: "source",
+        }];
+        for path in self.chapters.iter().filter_map(|c| c.path.as_ref()) {
+            attachments.push(Attachment {
+                name: path.to_string_lossy().replace('\\', "/"),
+                data: fs::read(self.root.join(path))?,
+                mime_type: "text/markdown".to_string(),
+                description: "Markdown source".to_string(),
+                relationship: "source",
+            });
+        }
+        Ok(attachments)
+    }
+}
+
+fn flatten(entries: &[Entry], depth: usize, chapters: &mut Vec<Chapter>) {
+    for entry in entries {
+        match entry {
+            Entry::File(path) => chapters.push(Chapter {
+                title: title_from_path(path),
+                path: Some(path.clone()),
+                depth,
+                kind: ChapterKind::Numbered,
+            }),
+            Entry::Section {
+                file,
+                title,
+                chapters: children,
+            } => {
+                let title = title
+                    .clone()
+                    .or_else(|| file.as_deref().map(title_from_path))
+                    .unwrap_or_default();
+                chapters.push(Chapter {
+                    title,
+                    path: file.clone(),
+                    depth,
+                    kind: ChapterKind::Numbered,
+                });
+                flatten(children, depth + 1, chapters);
+            }
+        }
+    }
+}
+
+fn title_from_path(path: &Path) -> String {
+    path.file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// A label for a chapter from its path: `guide/setup.md` becomes `guide-setup`
+fn chapter_label(path: &Path) -> String {
+    normalize(&path.with_extension(""))
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
+fn merged(base: &Mapping, overlay: &Mapping) -> Mapping {
+    let mut mapping = base.clone();
+    mapping.extend(overlay.clone());
+    mapping
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let summary = dir.path().join("SUMMARY.md");
+        fs::write(&summary, "- [Intro](intro.md)\n").unwrap();
+        assert!(is_manifest(&dir.path().join("book.yaml")));
+        assert!(!is_manifest(&summary));
+        assert!(!is_manifest(&dir.path().join("notes/summary.md")));
+
+        fs::write(dir.path().join("book.toml"), "[book]\n").unwrap();
+        assert!(is_manifest(&summary));
+        assert!(!is_manifest(&dir.path().join("summary.md")));
+    }
+
+    #[test]
+    fn test_load_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("book.yaml");
+        fs::write(
+            &manifest,
+            "title: Manual\nnumbering: \"1.a\"\nchapters:\n  - intro.md\n  - title: Guide\n    file: guide/index.md\n    chapters:\n      - guide/setup.md\n",
+        )
+        .unwrap();
+
+        let book = Book::load(&manifest).unwrap();
+        assert_eq!(book.numbering.as_deref(), Some("1.a"));
+        assert_eq!(book.metadata.get("title"), Some(&Value::from("Manual")));
+        let depths: Vec<_> = book
+            .chapters
+            .iter()
+            .map(|c| (c.title.as_str(), c.depth))
+            .collect();
+        assert_eq!(depths, vec![("intro", 0), ("Guide", 0), ("setup", 1)]);
+    }
+
+    #[test]
+    fn test_book_to_typst() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("guide")).unwrap();
+        fs::write(
+            dir.path().join("intro.md"),
+            "# Intro\n\nRead [setup](guide/setup.md). <font>Soon</font>\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("guide/setup.md"),
+            "---\nversion: 1.0\n---\n# Setup\n\nVersion {{ version }}, see [intro](../intro.md#intro).\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("book.yaml"),
+            "title: Manual\nchapters:\n  - file: intro.md\n    chapters:\n      - guide/setup.md\n",
+        )
+        .unwrap();
+
+        let book = Book::load(&dir.path().join("book.yaml")).unwrap();
+        let converted = book.to_typst(&Config::default()).unwrap();
+        let typst = converted.output;
+
+        assert!(typst.contains("#set heading(numbering: \"1.1\")"));
+        assert!(typst.contains("#outline()"));
+        assert!(typst.contains("= Intro <intro:intro>"));
+        assert!(typst.contains("== Setup <guide-setup:setup>"));
+        assert!(typst.contains("#link(<guide-setup>)[setup]"));
+        assert!(typst.contains("#link(<intro:intro>)[intro]"));
+        assert!(typst.contains("Version 1.0"));
+        assert_eq!(
+            converted.warnings,
+            ["intro.md: unsupported HTML `<font>` left out"]
+        );
+    }
+
+    #[test]
+    fn test_chapter_errors_name_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("book.yaml"), "chapters:\n  - missing.md\n").unwrap();
+        let book = Book::load(&dir.path().join("book.yaml")).unwrap();
+        let err = book.to_typst(&Config::default()).unwrap_err();
+        assert!(err.to_string().contains("missing.md"));
+    }
+}
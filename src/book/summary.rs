@@ -0,0 +1,114 @@
+use super::{Chapter, ChapterKind};
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag, TagEnd};
+use std::path::PathBuf;
+
+/// Parse an mdBook `SUMMARY.md` into chapters
+///
+/// Links in nested lists become numbered chapters at their list depth; links
+/// outside lists are unnumbered prefix and suffix chapters. Headings are part
+/// titles, except a leading title, and links with an empty target are draft
+/// chapters, kept as headings without content.
+pub fn parse(summary: &str) -> Vec<Chapter> {
+    let mut chapters = Vec::new();
+    let mut depth = 0usize;
+    let mut link: Option<(String, String)> = None;
+    let mut part: Option<String> = None;
+    let mut titled = false;
+
+    for event in Parser::new(summary) {
+        match event {
+            Event::Start(Tag::List(_)) => depth += 1,
+            Event::End(TagEnd::List(_)) => depth = depth.saturating_sub(1),
+            Event::Start(Tag::Heading {
+                level: HeadingLevel::H1,
+                ..
+            }) => part = Some(String::new()),
+            Event::End(TagEnd::Heading(HeadingLevel::H1)) => {
+                // A heading before any chapter is the title of the summary itself
+                let is_title = !titled && chapters.is_empty();
+                titled = true;
+                if let Some(title) = part.take().filter(|_| !is_title) {
+                    chapters.push(Chapter {
+                        title,
+                        path: None,
+                        depth: 0,
+                        kind: ChapterKind::Part,
+                    });
+                }
+            }
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                link = Some((dest_url.to_string(), String::new()));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, title)) = &mut link {
+                    title.push_str(&text);
+                } else if let Some(title) = &mut part {
+                    title.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Link) => {
+                if let Some((dest, title)) = link.take() {
+                    let path = dest.split('#').next().unwrap_or_default();
+                    chapters.push(Chapter {
+                        title,
+                        path: (!path.is_empty()).then(|| PathBuf::from(path)),
+                        depth: depth.saturating_sub(1),
+                        kind: if depth == 0 {
+                            ChapterKind::Unnumbered
+                        } else {
+                            ChapterKind::Numbered
+                        },
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    chapters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_summary() {
+        let summary = "# Summary\n\n[Introduction](intro.md)\n\n- [Guide](guide/index.md)\n  - [Setup](guide/setup.md)\n- [Reference](ref.md)\n";
+        let chapters = parse(summary);
+        let outline: Vec<_> = chapters
+            .iter()
+            .map(|c| (c.title.as_str(), c.depth))
+            .collect();
+        assert_eq!(
+            outline,
+            vec![
+                ("Introduction", 0),
+                ("Guide", 0),
+                ("Setup", 1),
+                ("Reference", 0)
+            ]
+        );
+        assert_eq!(chapters[0].kind, ChapterKind::Unnumbered);
+        assert_eq!(chapters[2].path, Some(PathBuf::from("guide/setup.md")));
+    }
+
+    #[test]
+    fn test_parts_and_drafts() {
+        let summary = "# Summary\n\n[Preface](preface.md)\n\n# User Guide\n\n- [Install](install.md)\n- [Roadmap]()\n\n---\n\n[Contributors](contributors.md)\n";
+        let chapters: Vec<_> = parse(summary)
+            .into_iter()
+            .map(|c| (c.title, c.path.is_some(), c.kind))
+            .collect();
+        assert_eq!(
+            chapters,
+            vec![
+                ("Preface".to_string(), true, ChapterKind::Unnumbered),
+                ("User Guide".to_string(), false, ChapterKind::Part),
+                ("Install".to_string(), true, ChapterKind::Numbered),
+                ("Roadmap".to_string(), false, ChapterKind::Numbered),
+                ("Contributors".to_string(), true, ChapterKind::Unnumbered),
+            ]
+        );
+    }
+}
@@ -1,38 +1,455 @@
+pub mod paper;
 pub mod themes;
 
+use crate::Md2PdfError;
+use paper::{is_length, Paper};
 use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use themes::Margin;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Typst paper name (`a4`, `us-letter`, `a5`) or custom size (`210mm x 99mm`)
     pub paper_size: String,
-    pub theme: String,
+    /// Turn the page so that it is wider than it is tall
+    #[serde(default)]
+    pub landscape: bool,
+    /// Built-in theme name, path to a `.typ` file, or a theme in `theme_dirs`;
+    /// overrides the document's `theme`, which falls back to `default`
+    #[serde(default)]
+    pub theme: Option<String>,
+    /// Directories searched for named themes, as `<name>.toml` or `<name>.typ`
+    #[serde(default)]
+    pub theme_dirs: Vec<PathBuf>,
+    /// Settings applied over the theme and the document's `style`
+    #[serde(default)]
+    pub style: Style,
     pub verbose: bool,
+    /// Metadata merged over the document's own frontmatter
+    #[serde(default)]
+    pub metadata: Mapping,
+    /// Pin dates and PDF identifiers so identical input gives identical bytes
+    #[serde(default)]
+    pub reproducible: bool,
+    /// PDF/A and PDF/UA standards the output must conform to
+    #[serde(default)]
+    pub pdf_standards: Vec<PdfStandard>,
+    /// Directory that images and other relative paths resolve against
+    #[serde(default)]
+    pub base_dir: Option<PathBuf>,
+    /// Read input files as book manifests, such as a `SUMMARY.md` that has
+    /// no `book.toml` next to it
+    #[serde(default)]
+    pub book: bool,
+    /// Attach the Markdown source to the PDF
+    #[serde(default)]
+    pub embed_source: bool,
+    /// With `embed_source`, also attach the local images it references
+    #[serde(default)]
+    pub embed_assets: bool,
+    /// Output file format
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// Resolution of PNG output, in pixels per inch
+    #[serde(default = "default_dpi")]
+    pub dpi: f32,
+    /// Pages to export; every page when empty
+    #[serde(default)]
+    pub pages: Vec<PageRange>,
+    /// Show unsupported HTML as text rather than leaving it out
+    #[serde(default)]
+    pub keep_html: bool,
+    /// Insert `{=typst}` code and `<!--typst -->` comments verbatim; turn off
+    /// for untrusted input
+    #[serde(default = "default_raw_typst")]
+    pub raw_typst: bool,
+}
+
+fn default_dpi() -> f32 {
+    144.0
+}
+
+fn default_raw_typst() -> bool {
+    true
+}
+
+/// Theme settings a single document can override, in `style:` frontmatter
+///
+/// Lengths and colors are Typst values, as in theme files.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Style {
+    pub font: Option<String>,
+    pub font_size: Option<String>,
+    pub text_color: Option<String>,
+    /// One length, or a mapping of sides such as `top` and `x` to lengths
+    pub margin: Option<Margin>,
+    /// Number of text columns on each page
+    #[serde(deserialize_with = "count")]
+    pub columns: Option<u32>,
+    pub justify: Option<bool>,
+    pub leading: Option<String>,
+    pub paragraph_spacing: Option<String>,
+    pub heading_numbering: Option<String>,
+    pub heading_font: Option<String>,
+    pub heading_color: Option<String>,
+    pub code_font: Option<String>,
+    pub link_color: Option<String>,
+    pub header: Option<Marginal>,
+    pub footer: Option<Marginal>,
+}
+
+/// A page header or footer: up to three templates, laid out left, center
+/// and right
+///
+/// Templates may contain `{{ title }}`, `{{ author }}`, `{{ date }}`,
+/// `{{ chapter }}` (the current level 1 heading), `{{ page }}` and
+/// `{{ total }}`. When any `even_*` template is set, even pages use the
+/// `even_*` templates, for two-sided printing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Marginal {
+    pub left: Option<String>,
+    pub center: Option<String>,
+    pub right: Option<String>,
+    pub even_left: Option<String>,
+    pub even_center: Option<String>,
+    pub even_right: Option<String>,
+    /// Whether to show it on the first page (default: true)
+    pub first_page: Option<bool>,
+}
+
+impl Marginal {
+    /// These templates with those set in `overlay` replacing them
+    pub fn overlaid(&self, overlay: &Marginal) -> Marginal {
+        let pick =
+            |overlay: &Option<String>, base: &Option<String>| overlay.clone().or(base.clone());
+        Marginal {
+            left: pick(&overlay.left, &self.left),
+            center: pick(&overlay.center, &self.center),
+            right: pick(&overlay.right, &self.right),
+            even_left: pick(&overlay.even_left, &self.even_left),
+            even_center: pick(&overlay.even_center, &self.even_center),
+            even_right: pick(&overlay.even_right, &self.even_right),
+            first_page: overlay.first_page.or(self.first_page),
+        }
+    }
+}
+
+impl Style {
+    /// Check that lengths and colors are plain values rather than Typst
+    /// expressions, which could run any code
+    pub fn check_values(&self) -> crate::Result<()> {
+        let lengths = [
+            ("font_size", &self.font_size),
+            ("leading", &self.leading),
+            ("paragraph_spacing", &self.paragraph_spacing),
+        ];
+        for (name, value) in lengths {
+            if let Some(value) = value.as_deref().filter(|value| !is_length(value)) {
+                return Err(Md2PdfError::InvalidInput(format!(
+                    "invalid {name} `{value}`; expected a length such as 11pt or 1.2em"
+                )));
+            }
+        }
+        let colors = [
+            ("text_color", &self.text_color),
+            ("heading_color", &self.heading_color),
+            ("link_color", &self.link_color),
+        ];
+        for (name, value) in colors {
+            if let Some(value) = value.as_deref().filter(|value| !themes::is_color(value)) {
+                return Err(Md2PdfError::InvalidInput(format!(
+                    "invalid {name} `{value}`; expected a color such as #336699 or blue"
+                )));
+            }
+        }
+        if let Some(margin) = &self.margin {
+            margin.to_typst()?;
+        }
+        Ok(())
+    }
+
+    /// These settings with those set in `overlay` replacing them
+    pub fn overlaid(&self, overlay: &Style) -> Style {
+        let pick =
+            |overlay: &Option<String>, base: &Option<String>| overlay.clone().or(base.clone());
+        Style {
+            font: pick(&overlay.font, &self.font),
+            font_size: pick(&overlay.font_size, &self.font_size),
+            text_color: pick(&overlay.text_color, &self.text_color),
+            margin: match (&self.margin, &overlay.margin) {
+                (Some(base), Some(overlay)) => Some(base.overlaid(overlay)),
+                (base, overlay) => overlay.clone().or(base.clone()),
+            },
+            columns: overlay.columns.or(self.columns),
+            justify: overlay.justify.or(self.justify),
+            leading: pick(&overlay.leading, &self.leading),
+            paragraph_spacing: pick(&overlay.paragraph_spacing, &self.paragraph_spacing),
+            heading_numbering: pick(&overlay.heading_numbering, &self.heading_numbering),
+            heading_font: pick(&overlay.heading_font, &self.heading_font),
+            heading_color: pick(&overlay.heading_color, &self.heading_color),
+            code_font: pick(&overlay.code_font, &self.code_font),
+            link_color: pick(&overlay.link_color, &self.link_color),
+            header: overlay_marginal(&self.header, &overlay.header),
+            footer: overlay_marginal(&self.footer, &overlay.footer),
+        }
+    }
+}
+
+/// A count given as a number or, from the command line, as a string
+pub(crate) fn count<'de, D>(deserializer: D) -> std::result::Result<Option<u32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Count {
+        Number(u32),
+        Text(String),
+    }
+
+    match Option::<Count>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Count::Number(n)) => Ok(Some(n)),
+        Some(Count::Text(text)) => text.trim().parse().map(Some).map_err(|_| {
+            serde::de::Error::custom(format!("expected a whole number, got `{text}`"))
+        }),
+    }
+}
+
+pub(crate) fn overlay_marginal(
+    base: &Option<Marginal>,
+    overlay: &Option<Marginal>,
+) -> Option<Marginal> {
+    match (base, overlay) {
+        (Some(base), Some(overlay)) => Some(base.overlaid(overlay)),
+        (base, overlay) => overlay.clone().or(base.clone()),
+    }
+}
+
+/// Output file formats: one PDF, or one PNG or SVG file per page
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Pdf,
+    Png,
+    Svg,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Pdf => "pdf",
+            Self::Png => "png",
+            Self::Svg => "svg",
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pdf" => Ok(Self::Pdf),
+            "png" => Ok(Self::Png),
+            "svg" => Ok(Self::Svg),
+            _ => Err(format!(
+                "unknown output format `{s}` (expected pdf, png or svg)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// An inclusive range of 1-based page numbers; open ends run to the first or last page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageRange {
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+}
+
+impl PageRange {
+    pub fn contains(&self, page: usize) -> bool {
+        self.start.is_none_or(|start| page >= start) && self.end.is_none_or(|end| page <= end)
+    }
+}
+
+impl FromStr for PageRange {
+    type Err = String;
+
+    /// Parse `3`, `1-3`, `5-` or `-2`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |part: &str| -> Result<Option<usize>, String> {
+            let part = part.trim();
+            if part.is_empty() {
+                return Ok(None);
+            }
+            match part.parse::<usize>() {
+                Ok(page) if page > 0 => Ok(Some(page)),
+                _ => Err(format!("invalid page number `{part}` in `{s}`")),
+            }
+        };
+
+        let range = match s.split_once('-') {
+            Some((start, end)) => Self {
+                start: number(start)?,
+                end: number(end)?,
+            },
+            None => {
+                let page = number(s)?.ok_or_else(|| "empty page range".to_string())?;
+                Self {
+                    start: Some(page),
+                    end: Some(page),
+                }
+            }
+        };
+
+        match (range.start, range.end) {
+            (Some(start), Some(end)) if start > end => {
+                Err(format!("page range `{s}` ends before it starts"))
+            }
+            _ => Ok(range),
+        }
+    }
+}
+
+/// Archival and accessibility standards supported for PDF output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PdfStandard {
+    /// PDF/A-2b: visual preservation for archiving
+    #[serde(rename = "a-2b")]
+    A2b,
+    /// PDF/A-3b: like PDF/A-2b, but allows embedded files
+    #[serde(rename = "a-3b")]
+    A3b,
+    /// PDF/UA-1: tagged, accessible PDF
+    #[serde(rename = "ua-1")]
+    Ua1,
+}
+
+impl FromStr for PdfStandard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "a-2b" => Ok(Self::A2b),
+            "a-3b" => Ok(Self::A3b),
+            "ua-1" => Ok(Self::Ua1),
+            _ => Err(format!(
+                "unknown PDF standard `{s}` (expected a-2b, a-3b or ua-1)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PdfStandard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::A2b => "PDF/A-2b",
+            Self::A3b => "PDF/A-3b",
+            Self::Ua1 => "PDF/UA-1",
+        })
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             paper_size: "a4".to_string(),
-            theme: "default".to_string(),
+            landscape: false,
+            theme: None,
+            theme_dirs: Vec::new(),
+            style: Style::default(),
             verbose: false,
+            metadata: Mapping::new(),
+            reproducible: false,
+            pdf_standards: Vec::new(),
+            base_dir: None,
+            book: false,
+            embed_source: false,
+            embed_assets: false,
+            format: OutputFormat::Pdf,
+            dpi: default_dpi(),
+            pages: Vec::new(),
+            keep_html: false,
+            raw_typst: default_raw_typst(),
         }
     }
 }
 
 impl Config {
-    pub fn paper_dimensions(&self) -> (f64, f64) {
-        match self.paper_size.to_lowercase().as_str() {
-            "letter" => (8.5 * 72.0, 11.0 * 72.0),
-            "legal" => (8.5 * 72.0, 14.0 * 72.0),
-            _ => (210.0 * 2.83465, 297.0 * 2.83465), // A4 in points
+    /// This config with a document's `theme` and `style` applied under its own
+    ///
+    /// A relative theme path in the document resolves against `base_dir`.
+    /// With `raw_typst` off, a theme file and style values other than plain
+    /// lengths and colors are refused, as they would run Typst code.
+    pub fn with_document_style(&self, theme: Option<&str>, style: &Style) -> crate::Result<Config> {
+        let mut config = self.clone();
+        if config.theme.is_none() {
+            if let Some(theme) = theme.filter(|theme| !self.raw_typst && themes::is_path(theme)) {
+                return Err(Md2PdfError::Theme(format!(
+                    "theme file `{theme}` runs Typst code, which --no-raw-typst turns off; \
+                     name a built-in or installed theme instead"
+                )));
+            }
+            config.theme = theme.map(|theme| match &self.base_dir {
+                Some(dir) if themes::is_path(theme) && Path::new(theme).is_relative() => {
+                    dir.join(theme).to_string_lossy().into_owned()
+                }
+                _ => theme.to_string(),
+            });
         }
+        if !self.raw_typst {
+            style.check_values()?;
+        }
+        config.style = style.overlaid(&self.style);
+        Ok(config)
     }
 
-    pub fn paper_typst(&self) -> &str {
-        match self.paper_size.to_lowercase().as_str() {
-            "letter" => "us-letter",
-            "legal" => "us-legal",
-            _ => "a4",
-        }
+    /// The name or path of the theme in use
+    pub fn theme_name(&self) -> &str {
+        self.theme.as_deref().unwrap_or("default")
+    }
+
+    /// The page size, from `paper_size` and `landscape`
+    pub fn paper(&self) -> crate::Result<Paper> {
+        let paper: Paper = self.paper_size.parse()?;
+        Ok(if self.landscape {
+            paper.landscape()
+        } else {
+            paper
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_range() {
+        let range: PageRange = "1-3".parse().unwrap();
+        assert!(range.contains(1) && range.contains(3) && !range.contains(4));
+
+        let open: PageRange = "5-".parse().unwrap();
+        assert!(open.contains(100) && !open.contains(4));
+
+        let single: PageRange = "2".parse().unwrap();
+        assert!(single.contains(2) && !single.contains(1));
+
+        assert!("0".parse::<PageRange>().is_err());
+        assert!("3-1".parse::<PageRange>().is_err());
+        assert!("a-b".parse::<PageRange>().is_err());
     }
 }
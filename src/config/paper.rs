@@ -0,0 +1,344 @@
+use crate::utils::suggest::closest;
+use crate::{Md2PdfError, Result};
+use std::str::FromStr;
+
+/// Points per millimetre
+const MM: f64 = 72.0 / 25.4;
+
+/// Typst's paper names with their portrait width and height in millimetres
+const PAPERS: &[(&str, f64, f64)] = &[
+    // ISO 216 A series
+    ("a0", 841.0, 1189.0),
+    ("a1", 594.0, 841.0),
+    ("a2", 420.0, 594.0),
+    ("a3", 297.0, 420.0),
+    ("a4", 210.0, 297.0),
+    ("a5", 148.0, 210.0),
+    ("a6", 105.0, 148.0),
+    ("a7", 74.0, 105.0),
+    ("a8", 52.0, 74.0),
+    ("a9", 37.0, 52.0),
+    ("a10", 26.0, 37.0),
+    ("a11", 18.0, 26.0),
+    // ISO 216 B series
+    ("iso-b1", 707.0, 1000.0),
+    ("iso-b2", 500.0, 707.0),
+    ("iso-b3", 353.0, 500.0),
+    ("iso-b4", 250.0, 353.0),
+    ("iso-b5", 176.0, 250.0),
+    ("iso-b6", 125.0, 176.0),
+    ("iso-b7", 88.0, 125.0),
+    ("iso-b8", 62.0, 88.0),
+    // ISO 216 C series
+    ("iso-c3", 324.0, 458.0),
+    ("iso-c4", 229.0, 324.0),
+    ("iso-c5", 162.0, 229.0),
+    ("iso-c6", 114.0, 162.0),
+    ("iso-c7", 81.0, 114.0),
+    // DIN D series
+    ("din-d3", 272.0, 385.0),
+    ("din-d4", 192.0, 272.0),
+    ("din-d5", 136.0, 192.0),
+    ("din-d6", 96.0, 136.0),
+    // SIS
+    ("sis-g5", 169.0, 239.0),
+    ("sis-e5", 115.0, 220.0),
+    // ANSI
+    ("ansi-a", 216.0, 279.0),
+    ("ansi-b", 279.0, 432.0),
+    ("ansi-c", 432.0, 559.0),
+    ("ansi-d", 559.0, 864.0),
+    ("ansi-e", 864.0, 1118.0),
+    // Architectural
+    ("arch-a", 229.0, 305.0),
+    ("arch-b", 305.0, 457.0),
+    ("arch-c", 457.0, 610.0),
+    ("arch-d", 610.0, 914.0),
+    ("arch-e1", 762.0, 1067.0),
+    ("arch-e", 914.0, 1219.0),
+    // JIS B series
+    ("jis-b0", 1030.0, 1456.0),
+    ("jis-b1", 728.0, 1030.0),
+    ("jis-b2", 515.0, 728.0),
+    ("jis-b3", 364.0, 515.0),
+    ("jis-b4", 257.0, 364.0),
+    ("jis-b5", 182.0, 257.0),
+    ("jis-b6", 128.0, 182.0),
+    ("jis-b7", 91.0, 128.0),
+    ("jis-b8", 64.0, 91.0),
+    ("jis-b9", 45.0, 64.0),
+    ("jis-b10", 32.0, 45.0),
+    ("jis-b11", 22.0, 32.0),
+    // SAC D series
+    ("sac-d0", 764.0, 1064.0),
+    ("sac-d1", 532.0, 760.0),
+    ("sac-d2", 380.0, 528.0),
+    ("sac-d3", 264.0, 376.0),
+    ("sac-d4", 188.0, 260.0),
+    ("sac-d5", 130.0, 184.0),
+    ("sac-d6", 92.0, 126.0),
+    // ISO 7810 ID
+    ("iso-id-1", 85.6, 53.98),
+    ("iso-id-2", 74.0, 105.0),
+    ("iso-id-3", 88.0, 125.0),
+    // Asia
+    ("asia-f4", 210.0, 330.0),
+    // Japan
+    ("jp-shiroku-ban-4", 264.0, 379.0),
+    ("jp-shiroku-ban-5", 189.5, 262.0),
+    ("jp-shiroku-ban-6", 127.0, 188.0),
+    ("jp-kiku-4", 227.0, 306.0),
+    ("jp-kiku-5", 151.0, 227.0),
+    ("jp-business-card", 91.0, 55.0),
+    // China
+    ("cn-business-card", 90.0, 54.0),
+    // Europe
+    ("eu-business-card", 85.0, 55.0),
+    // France
+    ("fr-tellière", 340.0, 440.0),
+    ("fr-couronne-écriture", 360.0, 460.0),
+    ("fr-couronne-édition", 370.0, 470.0),
+    ("fr-raisin", 500.0, 650.0),
+    ("fr-carré", 450.0, 560.0),
+    ("fr-jésus", 560.0, 760.0),
+    // United Kingdom Imperial
+    ("uk-brief", 406.4, 342.9),
+    ("uk-draft", 254.0, 406.4),
+    ("uk-foolscap", 203.2, 330.2),
+    ("uk-quarto", 203.2, 254.0),
+    ("uk-crown", 508.0, 381.0),
+    ("uk-book-a", 111.0, 178.0),
+    ("uk-book-b", 129.0, 198.0),
+    // United States
+    ("us-letter", 215.9, 279.4),
+    ("us-legal", 215.9, 355.6),
+    ("us-tabloid", 279.4, 431.8),
+    ("us-executive", 184.15, 266.7),
+    ("us-foolscap-folio", 215.9, 342.9),
+    ("us-statement", 139.7, 215.9),
+    ("us-ledger", 431.8, 279.4),
+    ("us-oficio", 215.9, 340.36),
+    ("us-gov-letter", 203.2, 266.7),
+    ("us-gov-legal", 215.9, 330.2),
+    ("us-business-card", 88.9, 50.8),
+    ("us-digest", 139.7, 215.9),
+    ("us-trade", 152.4, 228.6),
+    // Newspapers
+    ("newspaper-compact", 280.0, 430.0),
+    ("newspaper-berliner", 315.0, 470.0),
+    ("newspaper-broadsheet", 381.0, 578.0),
+    // Presentation slides
+    ("presentation-16-9", 297.0, 167.0625),
+    ("presentation-4-3", 280.0, 210.0),
+];
+
+/// A page size: a Typst paper name or a custom width and height
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Paper {
+    /// Typst paper name, `None` for a custom size
+    pub name: Option<&'static str>,
+    /// Width in points, after orientation
+    pub width: f64,
+    /// Height in points, after orientation
+    pub height: f64,
+    /// Whether a named paper is turned to landscape
+    pub flipped: bool,
+}
+
+impl Paper {
+    /// Turn the page so that it is wider than it is tall
+    pub fn landscape(self) -> Self {
+        if self.width >= self.height {
+            return self;
+        }
+        Self {
+            width: self.height,
+            height: self.width,
+            flipped: self.name.is_some(),
+            ..self
+        }
+    }
+
+    /// Arguments for Typst's `page` function
+    pub fn page_arguments(&self) -> String {
+        match self.name {
+            Some(name) if self.flipped => format!("paper: \"{name}\", flipped: true"),
+            Some(name) => format!("paper: \"{name}\""),
+            None => format!("width: {:.2}pt, height: {:.2}pt", self.width, self.height),
+        }
+    }
+}
+
+impl FromStr for Paper {
+    type Err = Md2PdfError;
+
+    /// Parse a Typst paper name (`a5`, `us-executive`), `letter` or `legal`,
+    /// or a custom size such as `210mm x 99mm` or `6in x 9in`
+    fn from_str(s: &str) -> Result<Self> {
+        let lower = s.trim().to_lowercase();
+        let name = match lower.as_str() {
+            "letter" => "us-letter",
+            "legal" => "us-legal",
+            name => name,
+        };
+
+        // ISO B and C sizes, such as `b5`, may leave out the `iso-` prefix
+        let iso = format!("iso-{name}");
+        let paper = PAPERS
+            .iter()
+            .find(|(n, _, _)| *n == name)
+            .or_else(|| PAPERS.iter().find(|(n, _, _)| *n == iso));
+        if let Some(&(name, width, height)) = paper {
+            return Ok(Self {
+                name: Some(name),
+                width: width * MM,
+                height: height * MM,
+                flipped: false,
+            });
+        }
+
+        let custom = lower.starts_with(|c: char| c.is_ascii_digit());
+        if let Some((width, height)) = lower.split_once('x').filter(|_| custom) {
+            return Ok(Self {
+                name: None,
+                width: length(width, s)?,
+                height: length(height, s)?,
+                flipped: false,
+            });
+        }
+
+        let suggestion = closest(name, PAPERS.iter().map(|(n, _, _)| *n))
+            .map(|paper| format!(" (did you mean `{paper}`?)"))
+            .unwrap_or_default();
+        Err(Md2PdfError::InvalidInput(format!(
+            "unknown paper size `{s}`{suggestion}; use a Typst paper name such as a4, a5 or us-letter, or a size such as 210mm x 99mm"
+        )))
+    }
+}
+
+/// A positive length in `mm`, `cm`, `in` or `pt`, in points
+fn length(value: &str, paper: &str) -> Result<f64> {
+    let value = value.trim();
+    let invalid = || {
+        Md2PdfError::InvalidInput(format!(
+            "invalid paper size `{paper}`: `{value}` is not a length in mm, cm, in or pt"
+        ))
+    };
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .ok_or_else(invalid)?;
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let scale = match unit.trim() {
+        "mm" => MM,
+        "cm" => 10.0 * MM,
+        "in" => 72.0,
+        "pt" => 1.0,
+        _ => return Err(invalid()),
+    };
+    if number <= 0.0 {
+        return Err(invalid());
+    }
+    Ok(number * scale)
+}
+
+/// A margin as a Typst value, from `auto` or a length in `pt`, `mm`, `cm`,
+/// `in`, `em` or `%`; a space before the unit is dropped
+pub fn margin_length(value: &str) -> Result<String> {
+    let value = value.trim();
+    if value == "auto" {
+        return Ok(value.to_string());
+    }
+    ["pt", "mm", "cm", "in", "em", "%"]
+        .iter()
+        .find_map(|unit| Some(format!("{}{unit}", value.strip_suffix(unit)?.trim_end())))
+        .filter(|length| is_length(length))
+        .ok_or_else(|| {
+            Md2PdfError::InvalidInput(format!(
+                "invalid margin `{value}`; expected a length such as 2cm, 1in or 15mm"
+            ))
+        })
+}
+
+/// Whether `value` is a single length such as `11pt`, `1.2em` or `50%`
+pub fn is_length(value: &str) -> bool {
+    ["pt", "mm", "cm", "in", "em", "%"]
+        .iter()
+        .find_map(|unit| value.trim().strip_suffix(unit))
+        .is_some_and(|number| {
+            !number.is_empty()
+                && number.chars().all(|c| c.is_ascii_digit() || c == '.')
+                && number.parse::<f64>().is_ok()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_named_papers() {
+        let a5: Paper = "A5".parse().unwrap();
+        assert_eq!(a5.name, Some("a5"));
+        assert_eq!(a5.page_arguments(), "paper: \"a5\"");
+
+        let b5: Paper = "B5".parse().unwrap();
+        assert_eq!(b5.name, Some("iso-b5"));
+
+        let letter: Paper = "letter".parse().unwrap();
+        assert_eq!(letter.name, Some("us-letter"));
+        assert!((letter.width - 612.0).abs() < 0.01);
+
+        let landscape = letter.landscape();
+        assert!(landscape.width > landscape.height);
+        assert_eq!(
+            landscape.page_arguments(),
+            "paper: \"us-letter\", flipped: true"
+        );
+    }
+
+    #[test]
+    fn test_custom_sizes() {
+        let envelope: Paper = "210mm x 99mm".parse().unwrap();
+        assert_eq!(envelope.name, None);
+        assert_eq!(
+            envelope.page_arguments(),
+            "width: 595.28pt, height: 280.63pt"
+        );
+        // Already landscape
+        assert_eq!(envelope.landscape(), envelope);
+
+        let book: Paper = "6inx9in".parse().unwrap();
+        assert_eq!((book.width, book.height), (432.0, 648.0));
+        assert_eq!(
+            book.landscape().page_arguments(),
+            "width: 648.00pt, height: 432.00pt"
+        );
+    }
+
+    #[test]
+    fn test_invalid_papers() {
+        let err = "a44".parse::<Paper>().unwrap_err().to_string();
+        assert!(err.contains("did you mean `a4`?"), "{err}");
+        assert!("210mm x 99".parse::<Paper>().is_err());
+        assert!("0mm x 10mm".parse::<Paper>().is_err());
+        assert!("210furlong x 99mm".parse::<Paper>().is_err());
+    }
+
+    #[test]
+    fn test_margin_length() {
+        assert_eq!(margin_length("2cm").unwrap(), "2cm");
+        assert_eq!(margin_length(" 1.5 in").unwrap(), "1.5in");
+        assert_eq!(margin_length("auto").unwrap(), "auto");
+        assert!(margin_length("2 cm x").is_err());
+        assert!(margin_length("1 5cm").is_err());
+        assert!(margin_length("wide").is_err());
+    }
+
+    #[test]
+    fn test_is_length() {
+        assert!(is_length("11pt") && is_length("1.2em") && is_length("50%"));
+        assert!(!is_length("pt") && !is_length("-1pt") && !is_length("1e3pt"));
+        assert!(!is_length("10pt)\n#text(red)[x]\n#set text(size: 10pt"));
+    }
+}
@@ -1,199 +1,540 @@
-pub fn get_theme_preamble(theme: &str, paper: &str) -> String {
-    match theme {
-        "github" => github_theme(paper),
-        "academic" => academic_theme(paper),
-        "minimal" => minimal_theme(paper),
-        _ => default_theme(paper),
-    }
-}
-
-fn default_theme(paper: &str) -> String {
-    format!(
-        r##"#set page(paper: "{paper}", margin: (x: 2.5cm, y: 2.5cm))
-#set text(size: 11pt)
-#set heading(numbering: none)
-#set par(justify: true, leading: 0.65em)
-
-#show heading.where(level: 1): it => {{
-  set text(size: 20pt, weight: "bold")
-  block(above: 1.5em, below: 0.8em, it)
-}}
-
-#show heading.where(level: 2): it => {{
-  set text(size: 16pt, weight: "bold")
-  block(above: 1.3em, below: 0.6em, it)
-}}
-
-#show heading.where(level: 3): it => {{
-  set text(size: 13pt, weight: "bold")
-  block(above: 1.2em, below: 0.5em, it)
-}}
-
-#show raw.where(block: true): it => {{
-  set text(size: 9pt)
-  block(
-    fill: luma(245),
-    inset: 10pt,
-    radius: 4pt,
-    width: 100%,
-    it
-  )
-}}
-
-#show raw.where(block: false): it => {{
-  box(
-    fill: luma(240),
-    inset: (x: 3pt, y: 0pt),
-    outset: (y: 3pt),
-    radius: 2pt,
-    it
-  )
-}}
-
-#show link: it => {{
-  set text(fill: rgb("#0366d6"))
-  underline(it)
-}}
-"##
-    )
-}
-
-fn github_theme(paper: &str) -> String {
-    format!(
-        r##"#set page(paper: "{paper}", margin: (x: 2cm, y: 2cm))
-#set text(font: "Inter", size: 10.5pt)
-#set heading(numbering: none)
-#set par(justify: false, leading: 0.7em)
-
-#show heading.where(level: 1): it => {{
-  set text(size: 24pt, weight: "bold")
-  block(above: 1.2em, below: 0.5em, {{
-    it
-    line(length: 100%, stroke: 0.5pt + luma(200))
-  }})
-}}
-
-#show heading.where(level: 2): it => {{
-  set text(size: 18pt, weight: "bold")
-  block(above: 1.2em, below: 0.5em, {{
-    it
-    line(length: 100%, stroke: 0.5pt + luma(200))
-  }})
-}}
-
-#show heading.where(level: 3): it => {{
-  set text(size: 14pt, weight: "bold")
-  block(above: 1em, below: 0.4em, it)
-}}
-
-#show raw.where(block: true): it => {{
-  set text(font: "Fira Code", size: 9pt)
-  block(
-    fill: rgb("#f6f8fa"),
-    inset: 12pt,
-    radius: 6pt,
-    width: 100%,
-    it
-  )
-}}
-
-#show raw.where(block: false): it => {{
-  set text(font: "Fira Code", size: 9pt)
-  box(
-    fill: rgb("#f6f8fa"),
-    inset: (x: 4pt, y: 2pt),
-    radius: 3pt,
-    it
-  )
-}}
-
-#show link: it => {{
-  set text(fill: rgb("#0366d6"))
-  it
-}}
-"##
-    )
-}
-
-fn academic_theme(paper: &str) -> String {
-    format!(
-        r##"#set page(paper: "{paper}", margin: (x: 3cm, y: 3cm))
-#set text(size: 12pt)
-#set heading(numbering: "1.1")
-#set par(justify: true, leading: 0.8em, first-line-indent: 1em)
-
-#show heading.where(level: 1): it => {{
-  set text(size: 16pt, weight: "bold")
-  block(above: 2em, below: 1em, it)
-}}
-
-#show heading.where(level: 2): it => {{
-  set text(size: 14pt, weight: "bold")
-  block(above: 1.5em, below: 0.8em, it)
-}}
-
-#show heading.where(level: 3): it => {{
-  set text(size: 12pt, weight: "bold", style: "italic")
-  block(above: 1.2em, below: 0.6em, it)
-}}
-
-#show raw.where(block: true): it => {{
-  set text(font: "Menlo", size: 9pt)
-  block(
-    stroke: 0.5pt + luma(180),
-    inset: 10pt,
-    width: 100%,
-    it
-  )
-}}
-
-#show raw.where(block: false): it => {{
-  set text(font: "Menlo", size: 10pt)
-  it
-}}
-
-#show link: it => {{
-  set text(fill: blue)
-  it
-}}
-"##
-    )
-}
-
-fn minimal_theme(paper: &str) -> String {
-    format!(
-        r##"#set page(paper: "{paper}", margin: (x: 2cm, y: 2cm))
-#set text(font: "Inter", size: 11pt)
-#set heading(numbering: none)
-#set par(justify: false, leading: 0.65em)
-
-#show heading.where(level: 1): it => {{
-  set text(size: 18pt, weight: "medium")
-  block(above: 1.5em, below: 0.8em, it)
-}}
-
-#show heading.where(level: 2): it => {{
-  set text(size: 14pt, weight: "medium")
-  block(above: 1.2em, below: 0.6em, it)
-}}
-
-#show heading.where(level: 3): it => {{
-  set text(size: 12pt, weight: "medium")
-  block(above: 1em, below: 0.5em, it)
-}}
-
-#show raw.where(block: true): it => {{
-  set text(size: 9pt)
-  block(
-    inset: 10pt,
-    width: 100%,
-    it
-  )
-}}
-
-#show link: it => {{
-  underline(it)
-}}
-"##
-    )
+mod spec;
+
+pub(crate) use spec::{color, is_color};
+pub use spec::{Margin, ThemeSpec};
+
+use super::paper::Paper;
+use super::{Config, Marginal, Style};
+use crate::utils::suggest::closest;
+use crate::{Md2PdfError, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Themes built into the binary
+pub const BUILTIN_THEMES: [&str; 4] = ["default", "github", "academic", "minimal"];
+
+/// Cover page layouts built into the binary
+pub const BUILTIN_COVERS: [&str; 3] = ["classic", "banner", "full"];
+
+/// How many themes an `extends` chain may go through, to stop cycles
+const MAX_EXTENDS: usize = 8;
+
+/// Where a theme comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Theme {
+    Builtin(&'static str),
+    /// A TOML theme spec or a Typst preamble, given by path or found in a
+    /// theme directory
+    File(PathBuf),
+}
+
+impl Theme {
+    /// Look up `name` as a path to a `.toml` or `.typ` file, a built-in theme,
+    /// or `<name>.toml` or `<name>.typ` in one of `theme_dirs`, in that order
+    pub fn resolve(name: &str, theme_dirs: &[PathBuf]) -> Result<Self> {
+        if is_path(name) {
+            let path = PathBuf::from(name);
+            if !path.is_file() {
+                return Err(Md2PdfError::Theme(format!(
+                    "theme file {} does not exist",
+                    path.display()
+                )));
+            }
+            return Ok(Self::File(path));
+        }
+
+        if let Some(builtin) = BUILTIN_THEMES.iter().find(|&&b| b == name) {
+            return Ok(Self::Builtin(builtin));
+        }
+
+        if let Some(path) = theme_dirs
+            .iter()
+            .flat_map(|dir| ["toml", "typ"].map(|ext| dir.join(format!("{name}.{ext}"))))
+            .find(|path| path.is_file())
+        {
+            return Ok(Self::File(path));
+        }
+
+        let available = available_themes(theme_dirs);
+        let suggestion = closest(name, available.iter().map(String::as_str))
+            .map(|theme| format!(" (did you mean `{theme}`?)"))
+            .unwrap_or_default();
+        Err(Md2PdfError::Theme(format!(
+            "unknown theme `{name}`{suggestion}; available themes: {}",
+            available.join(", ")
+        )))
+    }
+
+    /// The files to watch for changes: the theme's own and those it extends
+    pub fn files(&self, theme_dirs: &[PathBuf]) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let mut theme = self.clone();
+        while let Self::File(path) = theme {
+            if files.contains(&path) || files.len() > MAX_EXTENDS {
+                break;
+            }
+            files.push(path.clone());
+            let parent = Self::load_table(&path)
+                .ok()
+                .and_then(|table| extends(&table, &path, theme_dirs).ok().flatten());
+            match parent {
+                Some(parent) => theme = parent,
+                None => break,
+            }
+        }
+        files
+    }
+
+    /// The Typst preamble, compiled from the theme spec or read from the
+    /// `.typ` file with placeholders filled in, and `config.style` applied
+    pub fn preamble(&self, config: &Config) -> Result<String> {
+        let paper = config.paper()?;
+        if let Self::File(path) = self {
+            if !is_spec(path) {
+                let template = fs::read_to_string(path).map_err(|e| {
+                    Md2PdfError::Theme(format!("cannot read {}: {e}", path.display()))
+                })?;
+                let mut preamble = fill_placeholders(&template, &paper)
+                    .map_err(|e| Md2PdfError::Theme(format!("{}: {e}", path.display())))?;
+                // Without a spec to merge into, the style goes in rules of its own
+                if config.style != Style::default() {
+                    let mut overrides = ThemeSpec::default();
+                    overrides.apply(&config.style);
+                    preamble.push('\n');
+                    preamble.push_str(&overrides.to_preamble(&paper)?);
+                }
+                return Ok(preamble);
+            }
+        }
+        let mut spec = self.spec(&config.theme_dirs)?;
+        spec.apply(&config.style);
+        spec.to_preamble(&paper)
+    }
+
+    /// The header and footer from the theme spec, with `config.style`
+    /// applied; Typst themes only have those set by the style
+    pub fn marginals(&self, config: &Config) -> Result<(Option<Marginal>, Option<Marginal>)> {
+        let mut spec = match self {
+            Self::File(path) if !is_spec(path) => ThemeSpec::default(),
+            _ => self.spec(&config.theme_dirs)?,
+        };
+        spec.apply(&config.style);
+        Ok((spec.header, spec.footer))
+    }
+
+    /// The number of text columns, from the theme spec and `config.style`
+    pub fn columns(&self, config: &Config) -> Result<u32> {
+        let columns = match self {
+            Self::File(path) if !is_spec(path) => None,
+            _ => self.spec(&config.theme_dirs)?.page.columns,
+        };
+        Ok(config.style.columns.or(columns).unwrap_or(1))
+    }
+
+    /// The Typst source of the cover layout `name`: a `.typ` file relative
+    /// to the document, one of the theme's `[covers]`, or a built-in cover
+    pub fn cover(&self, name: &str, config: &Config) -> Result<String> {
+        let covers = match self {
+            Self::File(path) if !is_spec(path) => BTreeMap::new(),
+            _ => self.spec(&config.theme_dirs)?.covers,
+        };
+
+        let path = if is_path(name) {
+            if !config.raw_typst {
+                return Err(Md2PdfError::Theme(format!(
+                    "cover file `{name}` runs Typst code, which --no-raw-typst turns off; \
+                     name a built-in or theme cover instead"
+                )));
+            }
+            Some(cover_file(name, config)?)
+        } else {
+            covers.get(name).map(PathBuf::from)
+        };
+        if let Some(path) = path {
+            return fs::read_to_string(&path).map_err(|e| {
+                Md2PdfError::Theme(format!("cannot read cover {}: {e}", path.display()))
+            });
+        }
+        if let Some(source) = builtin_cover(name) {
+            return Ok(source.to_string());
+        }
+
+        let mut available: Vec<&str> = BUILTIN_COVERS.to_vec();
+        available.extend(covers.keys().map(String::as_str));
+        available.sort();
+        available.dedup();
+        let suggestion = closest(name, available.iter().copied())
+            .map(|cover| format!(" (did you mean `{cover}`?)"))
+            .unwrap_or_default();
+        Err(Md2PdfError::Theme(format!(
+            "unknown cover `{name}`{suggestion}; available covers: {}",
+            available.join(", ")
+        )))
+    }
+
+    /// The theme spec, with everything it extends merged in
+    pub fn spec(&self, theme_dirs: &[PathBuf]) -> Result<ThemeSpec> {
+        let table = self.merged_table(theme_dirs, 0)?;
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| Md2PdfError::Theme(format!("{}: {e}", self.name())))
+    }
+
+    fn merged_table(&self, theme_dirs: &[PathBuf], depth: usize) -> Result<toml::Table> {
+        if depth > MAX_EXTENDS {
+            return Err(Md2PdfError::Theme(format!(
+                "{}: `extends` goes more than {MAX_EXTENDS} themes deep; is there a cycle?",
+                self.name()
+            )));
+        }
+
+        let (mut table, parent) = match self {
+            Self::Builtin(name) => (parse_table(builtin_source(name), name)?, None),
+            Self::File(path) if !is_spec(path) => {
+                return Err(Md2PdfError::Theme(format!(
+                    "{}: a Typst theme cannot be extended; only TOML themes can",
+                    path.display()
+                )))
+            }
+            Self::File(path) => {
+                let mut table = Self::load_table(path)?;
+                resolve_covers(&mut table, path);
+                // Theme files start from the default theme unless they say otherwise
+                let parent = extends(&table, path, theme_dirs)?.unwrap_or(Self::Builtin("default"));
+                (table, Some(parent))
+            }
+        };
+        table.remove("extends");
+
+        Ok(match parent {
+            Some(parent) => {
+                let mut base = parent.merged_table(theme_dirs, depth + 1)?;
+                merge_tables(&mut base, table);
+                base
+            }
+            None => table,
+        })
+    }
+
+    fn load_table(path: &Path) -> Result<toml::Table> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| Md2PdfError::Theme(format!("cannot read {}: {e}", path.display())))?;
+        parse_table(&text, &path.display().to_string())
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Self::Builtin(name) => name.to_string(),
+            Self::File(path) => path.display().to_string(),
+        }
+    }
+}
+
+/// The preamble for `config.theme`
+pub fn get_theme_preamble(config: &Config) -> Result<String> {
+    Theme::resolve(config.theme_name(), &config.theme_dirs)?.preamble(config)
+}
+
+/// The header and footer for `config.theme`
+pub fn get_marginals(config: &Config) -> Result<(Option<Marginal>, Option<Marginal>)> {
+    Theme::resolve(config.theme_name(), &config.theme_dirs)?.marginals(config)
+}
+
+/// The number of text columns for `config.theme`
+pub fn get_columns(config: &Config) -> Result<u32> {
+    Theme::resolve(config.theme_name(), &config.theme_dirs)?.columns(config)
+}
+
+/// The Typst source of cover layout `name` for `config.theme`
+pub fn get_cover(name: &str, config: &Config) -> Result<String> {
+    Theme::resolve(config.theme_name(), &config.theme_dirs)?.cover(name, config)
+}
+
+/// Built-in themes and the theme files in `theme_dirs`, sorted
+pub fn available_themes(theme_dirs: &[PathBuf]) -> Vec<String> {
+    let mut themes: Vec<String> = BUILTIN_THEMES.iter().map(|s| s.to_string()).collect();
+    for entry in theme_dirs
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .flatten()
+    {
+        let path = entry.path();
+        if path
+            .extension()
+            .is_some_and(|ext| ext == "typ" || ext == "toml")
+        {
+            if let Some(stem) = path.file_stem() {
+                themes.push(stem.to_string_lossy().into_owned());
+            }
+        }
+    }
+    themes.sort();
+    themes.dedup();
+    themes
+}
+
+/// A cover file named in a document, resolved against `config.base_dir` and
+/// refused if it lies outside it
+fn cover_file(name: &str, config: &Config) -> Result<PathBuf> {
+    let unreadable =
+        |e: std::io::Error| Md2PdfError::Theme(format!("cannot read cover {name}: {e}"));
+    let base_dir = config
+        .base_dir
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .canonicalize()
+        .map_err(unreadable)?;
+    let path = base_dir.join(name).canonicalize().map_err(unreadable)?;
+    if !path.starts_with(&base_dir) {
+        return Err(Md2PdfError::Theme(format!(
+            "cover `{name}` is outside the base directory {}",
+            base_dir.display()
+        )));
+    }
+    Ok(path)
+}
+
+pub(crate) fn is_path(name: &str) -> bool {
+    name.contains('/') || name.contains('\\') || name.ends_with(".typ") || name.ends_with(".toml")
+}
+
+fn is_spec(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "toml")
+}
+
+fn builtin_source(name: &str) -> &'static str {
+    match name {
+        "github" => include_str!("themes/github.toml"),
+        "academic" => include_str!("themes/academic.toml"),
+        "minimal" => include_str!("themes/minimal.toml"),
+        _ => include_str!("themes/default.toml"),
+    }
+}
+
+fn builtin_cover(name: &str) -> Option<&'static str> {
+    match name {
+        "classic" => Some(include_str!("themes/covers/classic.typ")),
+        "banner" => Some(include_str!("themes/covers/banner.typ")),
+        "full" => Some(include_str!("themes/covers/full.typ")),
+        _ => None,
+    }
+}
+
+/// Make the paths in a theme's `[covers]` relative to the theme file, so
+/// they still point at the right files once merged into another theme
+fn resolve_covers(table: &mut toml::Table, path: &Path) {
+    let Some(toml::Value::Table(covers)) = table.get_mut("covers") else {
+        return;
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for (_, value) in covers.iter_mut() {
+        if let Some(file) = value.as_str() {
+            *value = toml::Value::String(dir.join(file).to_string_lossy().into_owned());
+        }
+    }
+}
+
+fn parse_table(text: &str, name: &str) -> Result<toml::Table> {
+    text.parse()
+        .map_err(|e: toml::de::Error| Md2PdfError::Theme(format!("{name}: {e}")))
+}
+
+/// The theme named by `extends` in `table`, with paths relative to the
+/// theme file that names it
+fn extends(table: &toml::Table, path: &Path, theme_dirs: &[PathBuf]) -> Result<Option<Theme>> {
+    let Some(value) = table.get("extends") else {
+        return Ok(None);
+    };
+    let name = value.as_str().ok_or_else(|| {
+        Md2PdfError::Theme(format!("{}: `extends` must be a string", path.display()))
+    })?;
+    let relative = path.parent().unwrap_or(Path::new("")).join(name);
+    let name = if is_path(name) {
+        relative.to_string_lossy().into_owned()
+    } else {
+        name.to_string()
+    };
+    Theme::resolve(&name, theme_dirs).map(Some)
+}
+
+/// Merge `overlay` into `base`, key by key within nested tables
+fn merge_tables(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => {
+                merge_tables(base, overlay)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Replace `{{ paper }}`, `{{ flipped }}`, `{{ width }}` and `{{ height }}`;
+/// anything else in double braces is left alone, as it may be Typst code
+fn fill_placeholders(template: &str, paper: &Paper) -> std::result::Result<String, String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        let end = start + len + 2;
+        let value = match rest[start + 2..start + len].trim() {
+            "paper" => Some(
+                paper
+                    .name
+                    .ok_or("a custom paper size has no name for `{{ paper }}`; use `{{ width }}` and `{{ height }}`")?
+                    .to_string(),
+            ),
+            "flipped" => Some(paper.flipped.to_string()),
+            "width" => Some(format!("{:.2}pt", paper.width)),
+            "height" => Some(format!("{:.2}pt", paper.height)),
+            _ => None,
+        };
+        match value {
+            Some(value) => {
+                output.push_str(&rest[..start]);
+                output.push_str(&value);
+            }
+            None => output.push_str(&rest[..end]),
+        }
+        rest = &rest[end..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_theme_suggests_closest() {
+        let err = Theme::resolve("acadmic", &[]).unwrap_err().to_string();
+        assert!(err.contains("did you mean `academic`?"), "{err}");
+        assert!(err.contains("available themes: academic, default, github, minimal"));
+
+        let err = Theme::resolve("corporate", &[]).unwrap_err().to_string();
+        assert!(!err.contains("did you mean"), "{err}");
+    }
+
+    #[test]
+    fn test_theme_from_directory_and_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let brand = dir.path().join("brand.typ");
+        fs::write(
+            &brand,
+            "#set page(paper: \"{{ paper }}\")\n#let f(x) = {{x}}\n",
+        )
+        .unwrap();
+        let dirs = vec![dir.path().to_path_buf()];
+
+        let theme = Theme::resolve("brand", &dirs).unwrap();
+        assert_eq!(theme, Theme::File(brand.clone()));
+        assert!(available_themes(&dirs).contains(&"brand".to_string()));
+
+        let config = Config {
+            paper_size: "letter".to_string(),
+            ..Config::default()
+        };
+        let preamble = Theme::resolve(brand.to_str().unwrap(), &[])
+            .unwrap()
+            .preamble(&config)
+            .unwrap();
+        assert_eq!(
+            preamble,
+            "#set page(paper: \"us-letter\")\n#let f(x) = {{x}}\n"
+        );
+    }
+
+    #[test]
+    fn test_builtin_specs_compile() {
+        for name in BUILTIN_THEMES {
+            let preamble = Theme::Builtin(name).preamble(&Config::default()).unwrap();
+            assert!(preamble.starts_with("#set page(paper: \"a4\""), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_spec_extends_and_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base.toml");
+        fs::write(&base, "extends = \"github\"\n[link]\ncolor = \"#ff0000\"\n").unwrap();
+        let brand = dir.path().join("brand.toml");
+        fs::write(
+            &brand,
+            "extends = \"./base.toml\"\n[headings.h1]\nsize = \"30pt\"\n",
+        )
+        .unwrap();
+
+        let theme = Theme::resolve(brand.to_str().unwrap(), &[]).unwrap();
+        assert_eq!(
+            theme.files(&[]),
+            vec![brand.clone(), dir.path().join("./base.toml")]
+        );
+
+        let spec = theme.spec(&[]).unwrap();
+        assert_eq!(spec.headings.h1.size.as_deref(), Some("30pt"));
+        assert_eq!(spec.headings.h1.weight.as_deref(), Some("bold"));
+        assert_eq!(spec.text.font.as_deref(), Some("Inter"));
+        assert_eq!(spec.link.color.as_deref(), Some("#ff0000"));
+
+        let preamble = theme.preamble(&Config::default()).unwrap();
+        assert!(preamble.contains("set text(size: 30pt, weight: \"bold\")"));
+        assert!(preamble.contains("set text(fill: rgb(\"#ff0000\"))"));
+    }
+
+    #[test]
+    fn test_spec_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let typo = dir.path().join("typo.toml");
+        fs::write(&typo, "[text]\nsise = \"12pt\"\n").unwrap();
+        let err = Theme::File(typo).spec(&[]).unwrap_err().to_string();
+        assert!(err.contains("unknown field `sise`"), "{err}");
+
+        let cycle = dir.path().join("cycle.toml");
+        fs::write(&cycle, "extends = \"./cycle.toml\"\n").unwrap();
+        let err = Theme::File(cycle).spec(&[]).unwrap_err().to_string();
+        assert!(err.contains("cycle"), "{err}");
+    }
+
+    #[test]
+    fn test_covers() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("covers")).unwrap();
+        fs::write(dir.path().join("covers/brand.typ"), "#page[#cover.title]\n").unwrap();
+        let brand = dir.path().join("brand.toml");
+        fs::write(&brand, "[covers]\nbrand = \"covers/brand.typ\"\n").unwrap();
+        let config = Config {
+            theme: Some(brand.to_string_lossy().into_owned()),
+            ..Config::default()
+        };
+
+        assert_eq!(
+            get_cover("brand", &config).unwrap(),
+            "#page[#cover.title]\n"
+        );
+        assert!(get_cover("banner", &config)
+            .unwrap()
+            .contains("cover.title"));
+
+        let err = get_cover("banenr", &config).unwrap_err().to_string();
+        assert!(err.contains("did you mean `banner`?"), "{err}");
+        assert!(err.contains("available covers: banner, brand, classic, full"));
+
+        // A cover file named by the document stays in its directory
+        let config = Config {
+            base_dir: Some(dir.path().join("covers")),
+            ..Config::default()
+        };
+        assert!(get_cover("./brand.typ", &config).is_ok());
+        let err = get_cover("../brand.toml", &config).unwrap_err().to_string();
+        assert!(err.contains("cover `../brand.toml` is outside"), "{err}");
+    }
 }
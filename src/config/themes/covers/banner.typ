@@ -0,0 +1,31 @@
+// A band of color across the top with the title in it, the details below
+// and the classification in a strip along the bottom
+#page(columns: 1, margin: 0pt, header: none, footer: none)[
+  #block(
+    width: 100%,
+    height: 45%,
+    inset: (x: 2.5cm, y: 2cm),
+    fill: if cover.background != none { cover.background } else { rgb("#1f3a5f") },
+  )[
+    #set text(fill: white)
+    #if cover.logo != none { image(cover.logo, height: 2cm) }
+    #v(1fr)
+    #if cover.title != none { text(size: 32pt, weight: "bold", cover.title) }
+    #if cover.subtitle != none { v(0.4em); text(size: 16pt, cover.subtitle) }
+  ]
+  #block(width: 100%, inset: (x: 2.5cm, y: 1.5cm))[
+    #set text(size: 12pt)
+    #if cover.author != none [*#cover.author* \ ]
+    #if cover.organization != none [#cover.organization \ ]
+    #if cover.version != none [Version #cover.version \ ]
+    #if cover.date != none [#cover.date]
+  ]
+  #if cover.classification != none {
+    place(bottom, block(
+      width: 100%,
+      inset: 10pt,
+      fill: rgb("#b00020"),
+      align(center, text(fill: white, weight: "bold", upper(cover.classification))),
+    ))
+  }
+]
@@ -0,0 +1,24 @@
+// Centered title with the logo above it, and the classification as the
+// page header and footer
+#page(
+  columns: 1,
+  fill: cover.background,
+  header: if cover.classification != none {
+    align(center, text(size: 9pt, weight: "bold", fill: rgb("#b00020"), upper(cover.classification)))
+  },
+  footer: if cover.classification != none {
+    align(center, text(size: 9pt, weight: "bold", fill: rgb("#b00020"), upper(cover.classification)))
+  },
+)[
+  #set align(center)
+  #v(1fr)
+  #if cover.logo != none { image(cover.logo, width: 4cm); v(2em) }
+  #if cover.title != none { text(size: 30pt, weight: "bold", cover.title) }
+  #if cover.subtitle != none { v(0.6em); text(size: 16pt, cover.subtitle) }
+  #v(3em)
+  #if cover.author != none [#text(size: 14pt, cover.author) \ ]
+  #if cover.organization != none [#cover.organization]
+  #v(1fr)
+  #if cover.version != none [Version #cover.version \ ]
+  #if cover.date != none [#cover.date]
+]
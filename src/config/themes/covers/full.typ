@@ -0,0 +1,24 @@
+// White text on a page filled with color, the title low on the page and
+// the classification boxed in the top right corner
+#page(
+  columns: 1,
+  header: none,
+  footer: none,
+  fill: if cover.background != none { cover.background } else { rgb("#1f3a5f") },
+)[
+  #set text(fill: white)
+  #if cover.classification != none {
+    align(right, box(stroke: 1pt + white, inset: 6pt, text(weight: "bold", upper(cover.classification))))
+  }
+  #if cover.logo != none { image(cover.logo, width: 3cm) }
+  #v(1fr)
+  #if cover.title != none { text(size: 36pt, weight: "bold", cover.title) }
+  #if cover.subtitle != none { v(0.4em); text(size: 18pt, cover.subtitle) }
+  #v(1.5em)
+  #line(length: 30%, stroke: 2pt + white)
+  #v(1em)
+  #if cover.author != none [#cover.author \ ]
+  #if cover.organization != none [#cover.organization \ ]
+  #if cover.version != none [Version #cover.version \ ]
+  #if cover.date != none [#cover.date]
+]
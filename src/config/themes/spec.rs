@@ -0,0 +1,426 @@
+use crate::config::paper::{margin_length, Paper};
+use crate::config::{overlay_marginal, Marginal, Style};
+use crate::{Md2PdfError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A theme described in TOML, compiled to a Typst preamble
+///
+/// Every setting is optional; unset ones are left to the theme it extends,
+/// or to Typst's defaults. Lengths (`"11pt"`, `"1.2em"`) and other values
+/// are Typst expressions; colors may also be written as `"#rrggbb"`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeSpec {
+    /// Built-in theme, theme name or file this one overrides
+    pub extends: Option<String>,
+    pub page: PageStyle,
+    pub text: TextStyle,
+    pub paragraph: ParagraphStyle,
+    pub headings: HeadingStyles,
+    /// Code blocks
+    pub code: BoxStyle,
+    /// Code spans within a paragraph
+    pub inline_code: BoxStyle,
+    pub link: LinkStyle,
+    pub header: Option<Marginal>,
+    pub footer: Option<Marginal>,
+    /// Cover page layouts, by name: `.typ` files relative to the theme
+    pub covers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PageStyle {
+    pub margin: Option<Margin>,
+    pub columns: Option<u32>,
+}
+
+/// One margin for all sides, or per side: `top`, `bottom`, `left`, `right`,
+/// `x`, `y`, `inside`, `outside` or `rest`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Margin {
+    All(String),
+    Sides(BTreeMap<String, String>),
+}
+
+impl Margin {
+    const SIDES: [&'static str; 9] = [
+        "top", "bottom", "left", "right", "x", "y", "inside", "outside", "rest",
+    ];
+
+    /// The margin as a Typst value, checking sides and lengths
+    pub fn to_typst(&self) -> Result<String> {
+        match self {
+            Margin::All(length) => margin_length(length),
+            Margin::Sides(sides) => {
+                let mut pairs = Vec::new();
+                for (side, length) in sides {
+                    if !Self::SIDES.contains(&side.as_str()) {
+                        return Err(Md2PdfError::InvalidInput(format!(
+                            "unknown margin side `{side}`; expected one of {}",
+                            Self::SIDES.join(", ")
+                        )));
+                    }
+                    pairs.push(format!("{side}: {}", margin_length(length)?));
+                }
+                Ok(format!("({})", pairs.join(", ")))
+            }
+        }
+    }
+
+    /// This margin with the sides set in `overlay` replacing its own
+    pub fn overlaid(&self, overlay: &Margin) -> Margin {
+        match (self, overlay) {
+            (Margin::Sides(base), Margin::Sides(sides)) => {
+                let mut merged = base.clone();
+                merged.extend(sides.clone());
+                Margin::Sides(merged)
+            }
+            _ => overlay.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextStyle {
+    pub font: Option<String>,
+    pub size: Option<String>,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParagraphStyle {
+    pub justify: Option<bool>,
+    /// Space between lines
+    pub leading: Option<String>,
+    /// Space between paragraphs
+    pub spacing: Option<String>,
+    pub first_line_indent: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeadingStyles {
+    /// Numbering pattern such as `"1.1"`, or `"none"`
+    pub numbering: Option<String>,
+    pub h1: HeadingStyle,
+    pub h2: HeadingStyle,
+    pub h3: HeadingStyle,
+    pub h4: HeadingStyle,
+    pub h5: HeadingStyle,
+    pub h6: HeadingStyle,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeadingStyle {
+    pub font: Option<String>,
+    pub size: Option<String>,
+    /// `"bold"`, `"medium"`, or a number such as `600`
+    pub weight: Option<String>,
+    /// `"italic"` or `"normal"`
+    pub style: Option<String>,
+    pub color: Option<String>,
+    /// Space above and below the heading
+    pub above: Option<String>,
+    pub below: Option<String>,
+    /// Stroke of a rule under the heading, e.g. `"0.5pt + luma(200)"`
+    pub rule: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BoxStyle {
+    pub font: Option<String>,
+    pub size: Option<String>,
+    pub fill: Option<String>,
+    pub stroke: Option<String>,
+    pub inset: Option<String>,
+    pub outset: Option<String>,
+    pub radius: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LinkStyle {
+    pub color: Option<String>,
+    pub underline: Option<bool>,
+}
+
+impl ThemeSpec {
+    /// Apply a document's style settings over the theme's
+    pub fn apply(&mut self, style: &Style) {
+        let set = |target: &mut Option<String>, value: &Option<String>| {
+            if value.is_some() {
+                target.clone_from(value);
+            }
+        };
+        set(&mut self.text.font, &style.font);
+        set(&mut self.text.size, &style.font_size);
+        set(&mut self.text.color, &style.text_color);
+        self.page.margin = match (&self.page.margin, &style.margin) {
+            // Sides given by the style replace only those sides
+            (Some(Margin::Sides(base)), Some(Margin::Sides(sides))) => {
+                let mut merged = base.clone();
+                merged.extend(sides.clone());
+                Some(Margin::Sides(merged))
+            }
+            (base, overlay) => overlay.clone().or(base.clone()),
+        };
+        if style.columns.is_some() {
+            self.page.columns = style.columns;
+        }
+        if style.justify.is_some() {
+            self.paragraph.justify = style.justify;
+        }
+        set(&mut self.paragraph.leading, &style.leading);
+        set(&mut self.paragraph.spacing, &style.paragraph_spacing);
+        set(&mut self.headings.numbering, &style.heading_numbering);
+        let headings = &mut self.headings;
+        for heading in [
+            &mut headings.h1,
+            &mut headings.h2,
+            &mut headings.h3,
+            &mut headings.h4,
+            &mut headings.h5,
+            &mut headings.h6,
+        ] {
+            set(&mut heading.font, &style.heading_font);
+            set(&mut heading.color, &style.heading_color);
+        }
+        set(&mut self.code.font, &style.code_font);
+        set(&mut self.inline_code.font, &style.code_font);
+        set(&mut self.link.color, &style.link_color);
+        self.header = overlay_marginal(&self.header, &style.header);
+        self.footer = overlay_marginal(&self.footer, &style.footer);
+    }
+
+    /// The Typst preamble for this theme on `paper`, or an error for an
+    /// invalid margin
+    ///
+    /// The header and footer are not included, as they depend on the
+    /// document; see [`crate::transpiler::typst::marginals_rule`].
+    pub fn to_preamble(&self, paper: &Paper) -> Result<String> {
+        let mut rules = Vec::new();
+
+        let margin = self
+            .page
+            .margin
+            .as_ref()
+            .map(Margin::to_typst)
+            .transpose()?;
+        let mut page = paper.page_arguments();
+        if let Some(margin) = margin {
+            page.push_str(&format!(", margin: {margin}"));
+        }
+        match self.page.columns {
+            Some(0) => {
+                return Err(Md2PdfError::Theme(
+                    "`columns` must be at least 1".to_string(),
+                ))
+            }
+            Some(columns) if columns > 1 => page.push_str(&format!(", columns: {columns}")),
+            _ => {}
+        }
+        rules.push(format!("#set page({page})"));
+
+        let text = arguments(&[
+            ("font", self.text.font.as_deref().map(string)),
+            ("size", self.text.size.clone()),
+            ("fill", self.text.color.as_deref().map(color)),
+        ]);
+        if !text.is_empty() {
+            rules.push(format!("#set text({text})"));
+        }
+
+        if let Some(numbering) = &self.headings.numbering {
+            let numbering = match numbering.as_str() {
+                "none" => "none".to_string(),
+                pattern => string(pattern),
+            };
+            rules.push(format!("#set heading(numbering: {numbering})"));
+        }
+
+        let par = arguments(&[
+            ("justify", self.paragraph.justify.map(|b| b.to_string())),
+            ("leading", self.paragraph.leading.clone()),
+            ("spacing", self.paragraph.spacing.clone()),
+            (
+                "first-line-indent",
+                self.paragraph.first_line_indent.clone(),
+            ),
+        ]);
+        if !par.is_empty() {
+            rules.push(format!("#set par({par})"));
+        }
+
+        let mut output = rules.join("\n");
+        output.push('\n');
+
+        let headings = &self.headings;
+        for (level, style) in [
+            &headings.h1,
+            &headings.h2,
+            &headings.h3,
+            &headings.h4,
+            &headings.h5,
+            &headings.h6,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if let Some(rule) = heading_rule(level + 1, style) {
+                output.push('\n');
+                output.push_str(&rule);
+            }
+        }
+
+        if let Some(rule) = code_rule(&self.code, true) {
+            output.push('\n');
+            output.push_str(&rule);
+        }
+        if let Some(rule) = code_rule(&self.inline_code, false) {
+            output.push('\n');
+            output.push_str(&rule);
+        }
+
+        if self.link.color.is_some() || self.link.underline.is_some() {
+            let mut body = String::new();
+            if let Some(fill) = &self.link.color {
+                body.push_str(&format!("  set text(fill: {})\n", color(fill)));
+            }
+            if self.link.underline == Some(true) {
+                body.push_str("  underline(it)\n");
+            } else {
+                body.push_str("  it\n");
+            }
+            output.push_str(&format!("\n#show link: it => {{\n{body}}}\n"));
+        }
+
+        Ok(output)
+    }
+}
+
+fn heading_rule(level: usize, style: &HeadingStyle) -> Option<String> {
+    let text = arguments(&[
+        ("font", style.font.as_deref().map(string)),
+        ("size", style.size.clone()),
+        ("weight", style.weight.as_deref().map(weight)),
+        ("style", style.style.as_deref().map(string)),
+        ("fill", style.color.as_deref().map(color)),
+    ]);
+    let spacing = arguments(&[
+        ("above", style.above.clone()),
+        ("below", style.below.clone()),
+    ]);
+    if text.is_empty() && spacing.is_empty() && style.rule.is_none() {
+        return None;
+    }
+
+    let mut body = String::new();
+    if !text.is_empty() {
+        body.push_str(&format!("  set text({text})\n"));
+    }
+    let content = match &style.rule {
+        Some(stroke) => format!(
+            "{{\n    it\n    line(length: 100%, stroke: {})\n  }}",
+            color(stroke)
+        ),
+        None => "it".to_string(),
+    };
+    let spacing = if spacing.is_empty() {
+        spacing
+    } else {
+        format!("{spacing}, ")
+    };
+    body.push_str(&format!("  block({spacing}{content})\n"));
+
+    Some(format!(
+        "#show heading.where(level: {level}): it => {{\n{body}}}\n"
+    ))
+}
+
+fn code_rule(style: &BoxStyle, block: bool) -> Option<String> {
+    let text = arguments(&[
+        ("font", style.font.as_deref().map(string)),
+        ("size", style.size.clone()),
+    ]);
+    let mut frame = vec![
+        ("fill", style.fill.as_deref().map(color)),
+        ("stroke", style.stroke.as_deref().map(color)),
+        ("inset", style.inset.clone()),
+        ("outset", style.outset.clone()),
+        ("radius", style.radius.clone()),
+    ];
+    let framed = frame.iter().any(|(_, value)| value.is_some());
+    if text.is_empty() && !framed {
+        return None;
+    }
+
+    let mut body = String::new();
+    if !text.is_empty() {
+        body.push_str(&format!("  set text({text})\n"));
+    }
+    if block {
+        frame.push(("width", Some("100%".to_string())));
+        body.push_str(&format!("  block({}, it)\n", arguments(&frame)));
+    } else if framed {
+        body.push_str(&format!("  box({}, it)\n", arguments(&frame)));
+    } else {
+        body.push_str("  it\n");
+    }
+
+    Some(format!(
+        "#show raw.where(block: {block}): it => {{\n{body}}}\n"
+    ))
+}
+
+/// `name: value` pairs for the values that are set
+fn arguments(pairs: &[(&str, Option<String>)]) -> String {
+    pairs
+        .iter()
+        .filter_map(|(name, value)| value.as_ref().map(|value| format!("{name}: {value}")))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// `#rrggbb` as an `rgb` call; anything else is a Typst expression such as
+/// `blue`, `luma(200)` or `0.5pt + luma(200)`
+pub(crate) fn color(value: &str) -> String {
+    if value.starts_with('#') {
+        format!("rgb({})", string(value))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Whether `value` is a color that needs no Typst code: `#rgb`, `#rrggbb`,
+/// `#rrggbbaa` or one of Typst's named colors
+pub(crate) fn is_color(value: &str) -> bool {
+    const NAMED: [&str; 18] = [
+        "black", "gray", "silver", "white", "navy", "blue", "aqua", "teal", "eastern", "purple",
+        "fuchsia", "maroon", "red", "orange", "yellow", "olive", "green", "lime",
+    ];
+    match value.trim().strip_prefix('#') {
+        Some(hex) => {
+            matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => NAMED.contains(&value.trim()),
+    }
+}
+
+fn weight(value: &str) -> String {
+    if value.chars().all(|c| c.is_ascii_digit()) {
+        value.to_string()
+    } else {
+        string(value)
+    }
+}
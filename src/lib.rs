@@ -1,17 +1,22 @@
+pub mod book;
 pub mod config;
 pub mod parser;
 pub mod renderer;
 pub mod transpiler;
 pub mod utils;
+pub mod workflow;
 
+use pulldown_cmark::{Event, Tag};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 pub use config::Config;
-use parser::{frontmatter::Frontmatter, markdown::parse_markdown};
-use renderer::pdf::render_pdf;
+use parser::{frontmatter::Frontmatter, markdown::parse_markdown, variables::substitute};
+use renderer::pdf::{render_pdf, Attachment};
+use renderer::{render, Rendered};
 use transpiler::typst::to_typst;
+use utils::images::{is_local_image, is_remote_url, mime_type};
 
 #[derive(Error, Debug)]
 pub enum Md2PdfError {
@@ -21,28 +26,283 @@ pub enum Md2PdfError {
     #[error("YAML parsing error: {0}")]
     Yaml(#[from] serde_yaml::Error),
 
+    #[error("TOML parsing error: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("JSON parsing error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Frontmatter error on line {line}: {message}")]
+    Frontmatter { line: usize, message: String },
+
+    #[error("Template error on line {line}: {message}")]
+    Template { line: usize, message: String },
+
+    #[error("Theme error: {0}")]
+    Theme(String),
+
     #[error("Typst compilation error: {0}")]
     Typst(String),
 
+    #[error("PDF export error: {0}")]
+    Pdf(String),
+
+    #[error("Image export error: {0}")]
+    Image(String),
+
+    #[error("In {path}: {source}", path = .path.display())]
+    Chapter {
+        path: PathBuf,
+        #[source]
+        source: Box<Md2PdfError>,
+    },
+
     #[error("Invalid input: {0}")]
     InvalidInput(String),
 }
 
 pub type Result<T> = std::result::Result<T, Md2PdfError>;
 
-/// Convert a Markdown file to PDF
-pub fn convert_file(input: &Path, output: &Path, config: &Config) -> Result<()> {
-    let content = fs::read_to_string(input)?;
-    let pdf_bytes = convert(&content, config)?;
-    fs::write(output, pdf_bytes)?;
-    Ok(())
-}
-
-/// Convert Markdown content to PDF bytes
-pub fn convert(markdown: &str, config: &Config) -> Result<Vec<u8>> {
-    let (frontmatter, content) = Frontmatter::extract(markdown)?;
-    let events = parse_markdown(content);
-    let typst_code = to_typst(events, &frontmatter, config);
-    let pdf = render_pdf(&typst_code, config)?;
-    Ok(pdf)
+/// The output of a conversion and the problems that didn't stop it, such as
+/// unsupported HTML left out
+#[derive(Debug)]
+pub struct Converted<T> {
+    pub output: T,
+    /// Each warning once, in the order found
+    pub warnings: Vec<String>,
+}
+
+/// Convert a Markdown file or book manifest to `config.format`
+///
+/// Relative paths resolve against the file's directory unless
+/// `config.base_dir` is set. For PNG and SVG, `output` is a file name
+/// pattern (see [`Rendered::write`]). Returns the conversion's warnings.
+pub fn convert_file(input: &Path, output: &Path, config: &Config) -> Result<Vec<String>> {
+    let converted = convert_file_to(input, config)?;
+    converted.output.write(output)?;
+    Ok(converted.warnings)
+}
+
+/// Convert a Markdown file to `config.format` in memory
+pub fn convert_file_to(input: &Path, config: &Config) -> Result<Converted<Rendered>> {
+    let (config, name) = file_context(input, config);
+    let prepared = prepare_file(input, &name, &config)?;
+    let output = render(
+        &prepared.typst_code,
+        prepared.markdown.as_deref(),
+        &config,
+        &prepared.attachments,
+    )?;
+    Ok(Converted {
+        output,
+        warnings: prepared.warnings,
+    })
+}
+
+/// The config for converting `input`, with `base_dir` defaulting to its
+/// directory, and its file name
+pub(crate) fn file_context(input: &Path, config: &Config) -> (Config, String) {
+    let mut config = config.clone();
+    if config.base_dir.is_none() {
+        config.base_dir = input.parent().map(Path::to_path_buf);
+    }
+    let name = input.file_name().map_or_else(
+        || "document.md".to_string(),
+        |n| n.to_string_lossy().into_owned(),
+    );
+    (config, name)
+}
+
+/// Write the Typst source generated for a Markdown file
+///
+/// Placed next to the input, the file compiles standalone with the `typst`
+/// CLI, since relative image paths resolve against its directory. Returns
+/// the conversion's warnings.
+pub fn convert_file_to_typst(input: &Path, output: &Path, config: &Config) -> Result<Vec<String>> {
+    let (config, _) = file_context(input, config);
+    let converted = if config.book || book::is_manifest(input) {
+        book::Book::load(input)?.to_typst(&config)?
+    } else {
+        convert_to_typst(&fs::read_to_string(input)?, &config)?
+    };
+    fs::write(output, converted.output)?;
+    Ok(converted.warnings)
+}
+
+/// Convert Markdown content to PDF bytes, whatever `config.format` says
+pub fn convert(markdown: &str, config: &Config) -> Result<Converted<Vec<u8>>> {
+    let prepared = prepare(markdown, Some("document.md"), config)?;
+    let output = render_pdf(
+        &prepared.typst_code,
+        prepared.markdown.as_deref(),
+        config,
+        &prepared.attachments,
+    )?;
+    Ok(Converted {
+        output,
+        warnings: prepared.warnings,
+    })
+}
+
+/// Convert Markdown content to `config.format`
+pub fn convert_to(markdown: &str, config: &Config) -> Result<Converted<Rendered>> {
+    let prepared = prepare(markdown, Some("document.md"), config)?;
+    let output = render(
+        &prepared.typst_code,
+        prepared.markdown.as_deref(),
+        config,
+        &prepared.attachments,
+    )?;
+    Ok(Converted {
+        output,
+        warnings: prepared.warnings,
+    })
+}
+
+/// Convert Markdown content to the Typst source that is compiled to PDF
+///
+/// The theme preamble and document metadata are included; files embedded with
+/// `embed_source` are not, as they are added at render time.
+pub fn convert_to_typst(markdown: &str, config: &Config) -> Result<Converted<String>> {
+    prepare(markdown, None, config).map(|prepared| Converted {
+        output: prepared.typst_code,
+        warnings: prepared.warnings,
+    })
+}
+
+/// Typst source generated from a file, ready to render
+pub(crate) struct Prepared {
+    pub typst_code: String,
+    pub attachments: Vec<Attachment>,
+    /// Markdown, included and theme files read, besides the input itself
+    pub sources: Vec<PathBuf>,
+    /// The Markdown of a single document, for errors to point at its lines
+    pub markdown: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Transpile a Markdown file or book manifest
+///
+/// `config` should come from [`file_context`].
+pub(crate) fn prepare_file(input: &Path, name: &str, config: &Config) -> Result<Prepared> {
+    if config.book || book::is_manifest(input) {
+        let book = book::Book::load(input)?;
+        let attachments = if config.embed_source {
+            book.attachments(input)?
+        } else {
+            Vec::new()
+        };
+        let styled = book.styled_config(config)?;
+        let (converted, includes) = book.transpile(config)?;
+        return Ok(Prepared {
+            typst_code: converted.output,
+            attachments,
+            sources: book
+                .files()
+                .chain(includes)
+                .chain(theme_files(&styled))
+                .collect(),
+            markdown: None,
+            warnings: converted.warnings,
+        });
+    }
+
+    prepare(&fs::read_to_string(input)?, Some(name), config)
+}
+
+/// The theme files used by the config's theme, if any
+pub(crate) fn theme_files(config: &Config) -> Vec<PathBuf> {
+    config::themes::Theme::resolve(config.theme_name(), &config.theme_dirs)
+        .map(|theme| theme.files(&config.theme_dirs))
+        .unwrap_or_default()
+}
+
+/// Transpile Markdown to Typst and collect the files to embed
+///
+/// `name` is the file name used when embedding the source; without it,
+/// nothing is embedded. `config.theme` and `config.style` take precedence
+/// over the theme and style in the frontmatter, as does `config.metadata`
+/// over the rest of it.
+pub(crate) fn prepare(markdown: &str, name: Option<&str>, config: &Config) -> Result<Prepared> {
+    let (mut frontmatter, content) = Frontmatter::extract(markdown)?;
+    frontmatter.merge(&config.metadata)?;
+    frontmatter.resolve_date(utils::date::today(config.reproducible))?;
+    let config = &config.with_document_style(frontmatter.theme.as_deref(), &frontmatter.style)?;
+    let first_line = markdown[..markdown.len() - content.len()]
+        .matches('\n')
+        .count()
+        + 1;
+    let content = substitute(content, &frontmatter.variables(), first_line)?;
+    let events = parse_markdown(&content);
+    let attachments = match name {
+        Some(name) => attachments(markdown, name, &events, config)?,
+        None => Vec::new(),
+    };
+    let converted = to_typst(events, &frontmatter, config)?;
+    Ok(Prepared {
+        typst_code: converted.output,
+        attachments,
+        sources: theme_files(config),
+        markdown: Some(markdown.to_string()),
+        warnings: converted.warnings,
+    })
+}
+
+/// The source Markdown and, optionally, the local images it references
+fn attachments(
+    markdown: &str,
+    name: &str,
+    events: &[Event<'_>],
+    config: &Config,
+) -> Result<Vec<Attachment>> {
+    if !config.embed_source {
+        return Ok(Vec::new());
+    }
+
+    let mut attachments = vec![Attachment {
+        name: name.to_string(),
+        data: markdown.as_bytes().to_vec(),
+        mime_type: "text/markdown".to_string(),
+        description: "Markdown source".to_string(),
+        relationship: "source",
+    }];
+
+    if config.embed_assets {
+        let base_dir = config.base_dir.as_deref().ok_or_else(|| {
+            Md2PdfError::InvalidInput("embedding assets requires a base directory".to_string())
+        })?;
+
+        for event in events {
+            if let Event::Start(Tag::Image { dest_url, .. }) = event {
+                if !is_local_image(dest_url) || is_remote_url(dest_url) {
+                    continue;
+                }
+                if attachments.iter().any(|a| a.name == dest_url.as_ref()) {
+                    continue;
+                }
+                attachments.push(Attachment {
+                    name: dest_url.to_string(),
+                    data: fs::read(asset_path(base_dir, dest_url)?)?,
+                    mime_type: mime_type(dest_url).to_string(),
+                    description: "Image referenced by the Markdown source".to_string(),
+                    relationship: "supplement",
+                });
+            }
+        }
+    }
+
+    Ok(attachments)
+}
+
+/// Resolve an image path against `base_dir`, refusing paths that lead out of it
+fn asset_path(base_dir: &Path, url: &str) -> Result<PathBuf> {
+    let base_dir = base_dir.canonicalize()?;
+    let path = base_dir.join(url).canonicalize()?;
+    if !path.starts_with(&base_dir) {
+        return Err(Md2PdfError::InvalidInput(format!(
+            "cannot embed `{url}`: it is outside the base directory {}",
+            base_dir.display()
+        )));
+    }
+    Ok(path)
 }
@@ -1,56 +1,408 @@
-use clap::Parser;
-use md2pdf_rs::{convert_file, Config};
-use std::path::PathBuf;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use md2pdf_rs::config::paper::Paper;
+use md2pdf_rs::config::{OutputFormat, PageRange, PdfStandard, Style};
+use md2pdf_rs::parser::frontmatter::Frontmatter;
+use md2pdf_rs::renderer::Rendered;
+use md2pdf_rs::workflow::batch::{self, is_pattern};
+use md2pdf_rs::workflow::serve::Server;
+use md2pdf_rs::workflow::watch::Watcher;
+use md2pdf_rs::{
+    convert_file, convert_file_to, convert_file_to_typst, convert_to, convert_to_typst, Config,
+    Md2PdfError,
+};
+use serde_yaml::{Mapping, Value};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::time::Instant;
 
 #[derive(Parser, Debug)]
 #[command(name = "md2pdf")]
 #[command(author, version, about = "Convert Markdown to PDF using Typst", long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// Input Markdown file
-    input: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// Output PDF file (defaults to input filename with .pdf extension)
+    /// Input Markdown files, directories or glob patterns, or `-` for stdin
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+
+    /// Output file, or `-` for stdout (defaults to input filename with the
+    /// format's extension, or stdout for stdin); for PNG and SVG, an `n` in
+    /// braces is replaced by the page number
     #[arg(short, long)]
     output: Option<PathBuf>,
 
-    /// Paper size (a4, letter, legal)
-    #[arg(long, default_value = "a4")]
+    /// Write outputs to this directory, mirroring the input directory structure
+    #[arg(long, value_name = "DIR", conflicts_with = "output")]
+    out_dir: Option<PathBuf>,
+
+    /// Number of files to convert in parallel (defaults to the number of CPUs)
+    #[arg(short, long, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// What to write: output (the rendered file), typst (the generated .typ source), or both
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "output")]
+    emit: Vec<Emit>,
+
+    /// Rebuild whenever the input or the files it uses change
+    #[arg(short, long, conflicts_with_all = ["out_dir", "emit"])]
+    watch: bool,
+
+    #[command(flatten)]
+    options: Options,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Preview a Markdown file in the browser, refreshing on every change
+    Serve {
+        /// Input Markdown file
+        input: PathBuf,
+
+        /// Port to listen on (localhost only)
+        #[arg(short, long, default_value_t = 8000)]
+        port: u16,
+
+        #[command(flatten)]
+        options: Options,
+    },
+}
+
+/// Settings shared by conversion and preview
+#[derive(Args, Debug)]
+struct Options {
+    /// Directory that images and other relative paths resolve against
+    /// (defaults to the input file's directory; required for assets with stdin)
+    #[arg(long, value_name = "DIR")]
+    base_dir: Option<PathBuf>,
+
+    /// Read the input as a book manifest, such as a SUMMARY.md without a book.toml
+    #[arg(long)]
+    book: bool,
+
+    /// Output format (pdf, png, svg)
+    #[arg(short, long, default_value = "pdf")]
+    format: OutputFormat,
+
+    /// Resolution of PNG output in pixels per inch
+    #[arg(long, default_value_t = 144.0)]
+    dpi: f32,
+
+    /// Pages to export, e.g. 1-3,5,8- (default: all)
+    #[arg(long, value_name = "RANGES", value_delimiter = ',')]
+    pages: Vec<PageRange>,
+
+    /// Paper size: a Typst paper name such as a4, a5, letter or us-executive, or WIDTHxHEIGHT such as 6inx9in
+    #[arg(long, default_value = "a4", value_parser = parse_paper)]
     paper: String,
 
-    /// Theme to use (default, github, academic, minimal)
-    #[arg(long, default_value = "default")]
-    theme: String,
+    /// Turn pages to landscape
+    #[arg(long)]
+    landscape: bool,
+
+    /// Theme: default, github, academic, minimal, a theme in a theme directory, or a path to a .toml or .typ file; overrides the frontmatter [default: default]
+    #[arg(long)]
+    theme: Option<String>,
+
+    /// Directory of .toml and .typ themes to search before ~/.config/md2pdf/themes (repeatable)
+    #[arg(long = "theme-dir", value_name = "DIR")]
+    theme_dirs: Vec<PathBuf>,
+
+    /// Set a style setting such as font_size, margin or footer.center, overriding the theme and frontmatter (repeatable)
+    #[arg(long = "style", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    styles: Vec<(String, String)>,
+
+    /// Set a metadata field, overriding the frontmatter (repeatable)
+    #[arg(short = 'M', long = "metadata", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    metadata: Vec<(String, String)>,
+
+    /// Read metadata from a YAML, TOML or JSON file, overriding the frontmatter (repeatable)
+    #[arg(long = "metadata-file", value_name = "FILE")]
+    metadata_files: Vec<PathBuf>,
+
+    /// PDF standard to conform to: a-2b, a-3b or ua-1 (repeatable)
+    #[arg(long = "pdf-standard", value_name = "STANDARD", value_delimiter = ',')]
+    pdf_standards: Vec<PdfStandard>,
+
+    /// Attach the Markdown source to the PDF (use with PDF/A-3b for archiving)
+    #[arg(long)]
+    embed_source: bool,
+
+    /// With --embed-source, also attach the local images the Markdown references
+    #[arg(long, requires = "embed_source")]
+    embed_assets: bool,
+
+    /// Show unsupported HTML tags as text instead of leaving them out
+    #[arg(long)]
+    keep_html: bool,
+
+    /// Treat {=typst} code and <!--typst --> comments as ordinary code and comments, for untrusted input
+    #[arg(long)]
+    no_raw_typst: bool,
+
+    /// Produce byte-identical output for identical input (honours SOURCE_DATE_EPOCH)
+    #[arg(long)]
+    reproducible: bool,
 
     /// Enable verbose output
     #[arg(short, long)]
     verbose: bool,
 }
 
+impl Options {
+    fn into_config(self) -> md2pdf_rs::Result<Config> {
+        Ok(Config {
+            paper_size: self.paper,
+            landscape: self.landscape,
+            theme: self.theme,
+            theme_dirs: self
+                .theme_dirs
+                .into_iter()
+                .chain(user_theme_dir())
+                .collect(),
+            style: style(self.styles)?,
+            verbose: self.verbose,
+            metadata: load_metadata(&self.metadata_files, self.metadata)?,
+            reproducible: self.reproducible,
+            pdf_standards: self.pdf_standards,
+            base_dir: self.base_dir,
+            book: self.book,
+            embed_source: self.embed_source,
+            embed_assets: self.embed_assets,
+            format: self.format,
+            dpi: self.dpi,
+            pages: self.pages,
+            keep_html: self.keep_html,
+            raw_typst: !self.no_raw_typst,
+        })
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Emit {
+    Output,
+    Typst,
+}
+
 fn main() {
     let cli = Cli::parse();
 
+    let emit_output = cli.emit.contains(&Emit::Output);
+    let emit_typst = cli.emit.contains(&Emit::Typst);
+
+    if let Some(Command::Serve {
+        input,
+        port,
+        options,
+    }) = cli.command
+    {
+        let mut config = exit_on_error(options.into_config());
+        if config.format == OutputFormat::Pdf {
+            config.format = OutputFormat::Svg;
+        }
+        serve(&input, port, &config);
+    }
+
+    let config = exit_on_error(cli.options.into_config());
+
+    let is_batch = cli.inputs.len() > 1
+        || cli.out_dir.is_some()
+        || cli
+            .inputs
+            .iter()
+            .any(|input| input.is_dir() || is_pattern(input));
+    if is_batch {
+        if cli.watch {
+            eprintln!("Error: --watch takes a single input file");
+            process::exit(1);
+        }
+        if cli.output.is_some() {
+            eprintln!("Error: --output takes a single input file; use --out-dir for several");
+            process::exit(1);
+        }
+        let threads = cli
+            .jobs
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+        let emit = (emit_output, emit_typst);
+        if !run_batch(&cli.inputs, cli.out_dir.as_deref(), threads, emit, &config) {
+            process::exit(1);
+        }
+        return;
+    }
+
+    let input = &cli.inputs[0];
+    let from_stdin = is_stdio(input);
+
+    if from_stdin && config.book {
+        eprintln!("Error: --book needs a manifest file, not stdin");
+        process::exit(1);
+    }
+    if from_stdin && emit_output && emit_typst {
+        eprintln!("Error: with input from stdin, emit typst and output in separate runs");
+        process::exit(1);
+    }
+
+    // With --emit typst alone, -o names the .typ file; otherwise it sits next
+    // to the input so relative image paths resolve when compiled standalone
+    let typst_output = match &cli.output {
+        Some(output) if !emit_output => output.clone(),
+        _ if from_stdin => PathBuf::from("-"),
+        _ => input.with_extension("typ"),
+    };
     let output = cli.output.unwrap_or_else(|| {
-        let mut out = cli.input.clone();
-        out.set_extension("pdf");
+        if from_stdin {
+            return PathBuf::from("-");
+        }
+        let mut out = input.clone();
+        out.set_extension(config.format.extension());
         out
     });
 
-    let config = Config {
-        paper_size: cli.paper,
-        theme: cli.theme,
-        verbose: cli.verbose,
+    if cli.watch {
+        if from_stdin || is_stdio(&output) {
+            eprintln!("Error: --watch needs an input and output file, not stdin or stdout");
+            process::exit(1);
+        }
+        watch(input, &output, &config);
+    }
+
+    let mut warnings = Vec::new();
+    if emit_typst {
+        warnings = run(input, &typst_output, &config, Emit::Typst);
+    }
+    // Both convert the same Markdown, so their warnings are the same
+    if emit_output {
+        warnings = run(input, &output, &config, Emit::Output);
+    }
+    print_warnings(input, warnings);
+}
+
+fn print_warnings(input: &Path, warnings: Vec<String>) {
+    for warning in warnings {
+        eprintln!("Warning: {}: {warning}", input.display());
+    }
+}
+
+fn exit_on_error<T>(result: md2pdf_rs::Result<T>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("Error: {e}");
+        process::exit(1);
+    })
+}
+
+fn serve(input: &Path, port: u16, config: &Config) -> ! {
+    let server = exit_on_error(Server::bind(port));
+    if let Ok(addr) = server.local_addr() {
+        eprintln!(
+            "Serving {} at http://{addr} (Ctrl+C to stop)",
+            input.display()
+        );
+    }
+    exit_on_error(server.run(Watcher::new(input, config)));
+    process::exit(0);
+}
+
+/// Rebuild on every change until interrupted; failed builds leave the last
+/// good output in place
+fn watch(input: &Path, output: &Path, config: &Config) -> ! {
+    let mut watcher = Watcher::new(input, config);
+    eprintln!("Watching {} (Ctrl+C to stop)", input.display());
+
+    loop {
+        let start = Instant::now();
+        let result = watcher.build().and_then(|converted| {
+            print_warnings(input, converted.warnings);
+            converted.output.write(output)
+        });
+        match result {
+            Ok(_) => eprintln!(
+                "Built {} in {:.2}s",
+                output.display(),
+                start.elapsed().as_secs_f64()
+            ),
+            Err(e) => eprintln!("Error: {e}\nKeeping the last good output"),
+        }
+
+        if config.verbose {
+            for path in watcher.watched() {
+                eprintln!("  watching {}", path.display());
+            }
+        }
+        watcher.wait_for_change();
+    }
+}
+
+/// Convert every input in parallel and print a summary; false if any failed
+fn run_batch(
+    inputs: &[PathBuf],
+    out_dir: Option<&Path>,
+    threads: usize,
+    (emit_output, emit_typst): (bool, bool),
+    config: &Config,
+) -> bool {
+    if inputs.iter().any(|input| is_stdio(input)) {
+        eprintln!("Error: `-` (stdin) cannot be combined with other inputs");
+        return false;
+    }
+
+    let extension = if emit_output {
+        config.format.extension()
+    } else {
+        "typ"
+    };
+    let jobs = match batch::plan(inputs, out_dir, extension) {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            return false;
+        }
     };
 
-    if cli.verbose {
-        eprintln!("Converting {} to {}", cli.input.display(), output.display());
+    let outcomes = batch::run(&jobs, threads, |job| {
+        let warnings = convert_job(job, (emit_output, emit_typst), config)?;
+        print_warnings(&job.input, warnings);
+        Ok(())
+    });
+
+    eprintln!("{}", batch::summary(&outcomes));
+    outcomes.iter().all(|outcome| outcome.result.is_ok())
+}
+
+fn convert_job(
+    job: &batch::Job,
+    (emit_output, emit_typst): (bool, bool),
+    config: &Config,
+) -> md2pdf_rs::Result<Vec<String>> {
+    let mut warnings = Vec::new();
+    if emit_typst {
+        let typst_output = if emit_output {
+            job.input.with_extension("typ")
+        } else {
+            job.output.clone()
+        };
+        warnings = convert_file_to_typst(&job.input, &typst_output, config)?;
+    }
+    if emit_output {
+        warnings = convert_file(&job.input, &job.output, config)?;
+    }
+    Ok(warnings)
+}
+
+/// Convert or exit on error, returning the conversion's warnings
+fn run(input: &Path, output: &Path, config: &Config, emit: Emit) -> Vec<String> {
+    if config.verbose {
+        eprintln!("Converting {} to {}", input.display(), output.display());
     }
 
-    match convert_file(&cli.input, &output, &config) {
-        Ok(()) => {
-            if cli.verbose {
+    match convert(input, output, config, emit) {
+        Ok(warnings) => {
+            if config.verbose {
                 eprintln!("Successfully created {}", output.display());
             }
+            warnings
         }
         Err(e) => {
             eprintln!("Error: {e}");
@@ -58,3 +410,132 @@ fn main() {
         }
     }
 }
+
+/// `-` stands for stdin as input and stdout as output
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+fn convert(
+    input: &Path,
+    output: &Path,
+    config: &Config,
+    emit: Emit,
+) -> md2pdf_rs::Result<Vec<String>> {
+    let converted = match (is_stdio(input), is_stdio(output), emit) {
+        (false, false, Emit::Output) => return convert_file(input, output, config),
+        (false, false, Emit::Typst) => return convert_file_to_typst(input, output, config),
+        (_, _, Emit::Typst) => {
+            let typst = convert_to_typst(&read_input(input)?, config)?;
+            write_output(output, typst.output.as_bytes())?;
+            return Ok(typst.warnings);
+        }
+        (true, _, Emit::Output) => convert_to(&read_input(input)?, config)?,
+        (false, true, Emit::Output) => convert_file_to(input, config)?,
+    };
+    finish(converted.output, output)?;
+    Ok(converted.warnings)
+}
+
+fn read_input(input: &Path) -> io::Result<String> {
+    if is_stdio(input) {
+        let mut markdown = String::new();
+        io::stdin().read_to_string(&mut markdown)?;
+        Ok(markdown)
+    } else {
+        fs::read_to_string(input)
+    }
+}
+
+fn write_output(output: &Path, data: &[u8]) -> md2pdf_rs::Result<()> {
+    if is_stdio(output) {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(data)?;
+        stdout.flush()?;
+    } else {
+        fs::write(output, data)?;
+    }
+    Ok(())
+}
+
+/// Write rendered output; stdout takes a single file
+fn finish(rendered: Rendered, output: &Path) -> md2pdf_rs::Result<()> {
+    if !is_stdio(output) {
+        rendered.write(output)?;
+        return Ok(());
+    }
+
+    match rendered {
+        Rendered::Document(data) => write_output(output, &data),
+        Rendered::Pages(pages) => match pages.as_slice() {
+            [(_, data)] => write_output(output, data),
+            _ => Err(Md2PdfError::InvalidInput(format!(
+                "{} pages cannot be written to stdout; select one with --pages or write to a file",
+                pages.len()
+            ))),
+        },
+    }
+}
+
+/// `md2pdf/themes` in the user's configuration directory
+fn user_theme_dir() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("md2pdf").join("themes"))
+}
+
+fn parse_paper(s: &str) -> Result<String, String> {
+    s.parse::<Paper>()
+        .map(|_| s.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got `{s}`"))
+}
+
+/// Metadata from files, then `-M` pairs, which override the frontmatter
+fn load_metadata(files: &[PathBuf], pairs: Vec<(String, String)>) -> md2pdf_rs::Result<Mapping> {
+    let mut metadata = Mapping::new();
+
+    for path in files {
+        metadata.extend(Frontmatter::load_metadata_file(path)?);
+    }
+
+    for (key, value) in pairs {
+        metadata.insert(Value::String(key), scalar(value));
+    }
+
+    Ok(metadata)
+}
+
+/// The style set by `--style` pairs
+fn style(pairs: Vec<(String, String)>) -> md2pdf_rs::Result<Style> {
+    let mut style = Mapping::new();
+    for (key, value) in pairs {
+        // `footer.center=...` sets one field of a nested setting
+        let mut keys: Vec<&str> = key.split('.').collect();
+        let last = keys.pop().unwrap_or_default();
+        let mut target = &mut style;
+        for key in keys {
+            let entry = target.entry(Value::from(key)).or_insert(Value::Null);
+            if !entry.is_mapping() {
+                *entry = Value::Mapping(Mapping::new());
+            }
+            target = entry.as_mapping_mut().expect("replaced with a mapping");
+        }
+        target.insert(Value::from(last), scalar(value));
+    }
+    Ok(serde_yaml::from_value(Value::Mapping(style))?)
+}
+
+fn scalar(value: String) -> Value {
+    match value.as_str() {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => Value::String(value),
+    }
+}
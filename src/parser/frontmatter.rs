@@ -1,5 +1,13 @@
-use crate::Result;
+use crate::config::{count, Style};
+use crate::parser::variables::Variables;
+use crate::utils::date;
+use crate::{Md2PdfError, Result};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Frontmatter {
@@ -9,28 +17,239 @@ pub struct Frontmatter {
     #[serde(default)]
     pub keywords: Vec<String>,
     pub abstract_text: Option<String>,
+    /// Use the build date when `date` is not set
+    #[serde(default)]
+    pub auto_date: bool,
+    /// strftime pattern for displaying dates, e.g. `"%-d %B %Y"`
+    pub date_format: Option<String>,
+    /// Document language (`fr`, `pt-BR`), used for hyphenation and month names
+    pub lang: Option<String>,
+    /// Theme for this document, in place of `--theme`
+    pub theme: Option<String>,
+    /// Theme settings for this document
+    #[serde(default)]
+    pub style: Style,
+    /// Number of text columns, the same as `style.columns`
+    #[serde(
+        default,
+        deserialize_with = "count",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub columns: Option<u32>,
+    /// Cover page layout: a built-in one, one from the theme, or a path to
+    /// a `.typ` file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover: Option<String>,
+    /// Any other keys, available as `{{ key }}` in the document body
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, Value>,
+}
+
+/// Serialization formats accepted for frontmatter and metadata files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl MetadataFormat {
+    /// Guess the format from a file extension, defaulting to YAML
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("toml") => Self::Toml,
+            Some("json") => Self::Json,
+            _ => Self::Yaml,
+        }
+    }
+
+    /// Parse metadata into a YAML value, whatever the source format
+    pub fn parse(self, text: &str) -> Result<Value> {
+        match self {
+            Self::Yaml => Ok(serde_yaml::from_str(text)?),
+            Self::Toml => Ok(toml_to_yaml(toml::from_str(text)?)),
+            Self::Json => Ok(serde_json::from_str(text)?),
+        }
+    }
 }
 
 impl Frontmatter {
     /// Extract frontmatter from markdown content
-    /// Returns (frontmatter, remaining_content)
+    ///
+    /// The frontmatter must start on the first line: `---` YAML (closed by
+    /// `---` or `...`), `+++` TOML, or a JSON object. Delimiters must sit on
+    /// their own lines. Returns (frontmatter, remaining_content), where the
+    /// remaining content is a suffix of `content` so offsets can be mapped back.
     pub fn extract(content: &str) -> Result<(Self, &str)> {
-        let content = content.trim_start();
+        let text = content.strip_prefix('\u{feff}').unwrap_or(content);
+        let (first, rest) = text.split_once('\n').unwrap_or((text, ""));
 
-        if !content.starts_with("---") {
-            return Ok((Self::default(), content));
+        let (format, metadata, remaining, first_line) = match first.trim_end() {
+            "---" => match split_at_delimiter(rest, &["---", "..."]) {
+                Some((metadata, remaining)) => (MetadataFormat::Yaml, metadata, remaining, 2),
+                None => return Ok((Self::default(), content)),
+            },
+            "+++" => match split_at_delimiter(rest, &["+++"]) {
+                Some((metadata, remaining)) => (MetadataFormat::Toml, metadata, remaining, 2),
+                None => return Ok((Self::default(), content)),
+            },
+            _ if is_json_object_start(text) => {
+                let mut stream = serde_json::Deserializer::from_str(text).into_iter::<Value>();
+                if let Some(Err(e)) = stream.next() {
+                    return Err(locate(e.into(), text, 1));
+                }
+                let end_pos = stream.byte_offset();
+                (MetadataFormat::Json, &text[..end_pos], &text[end_pos..], 1)
+            }
+            _ => return Ok((Self::default(), content)),
+        };
+
+        let frontmatter = match format {
+            MetadataFormat::Yaml => serde_yaml::from_str::<Option<Self>>(metadata)
+                .map(Option::unwrap_or_default)
+                .map_err(Md2PdfError::from),
+            _ => format.parse(metadata).and_then(Self::from_value),
         }
+        .map_err(|e| locate(e, metadata, first_line))?
+        .fold_columns();
+
+        Ok((frontmatter, skip_blank_lines(remaining)))
+    }
 
-        let after_first_delimiter = &content[3..];
-        let Some(end_pos) = after_first_delimiter.find("\n---") else {
-            return Ok((Self::default(), content));
+    /// Read a metadata file (YAML, TOML or JSON, by extension) as a mapping
+    pub fn load_metadata_file(path: &Path) -> Result<Mapping> {
+        let text = fs::read_to_string(path)?;
+        match MetadataFormat::from_path(path).parse(&text)? {
+            Value::Mapping(mapping) => Ok(mapping),
+            Value::Null => Ok(Mapping::new()),
+            _ => Err(Md2PdfError::InvalidInput(format!(
+                "{}: metadata file must contain a mapping",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Merge `overlay` over these fields; overlay values win, nested mappings are merged
+    pub fn merge(&mut self, overlay: &Mapping) -> Result<()> {
+        if overlay.is_empty() {
+            return Ok(());
+        }
+
+        let mut value = serde_yaml::to_value(&*self)?;
+        merge_values(&mut value, &Value::Mapping(overlay.clone()));
+        *self = Self::from_value(value)?;
+        Ok(())
+    }
+
+    fn from_value(value: Value) -> Result<Self> {
+        match value {
+            Value::Null => Ok(Self::default()),
+            value => Ok(serde_yaml::from_value::<Self>(value)?.fold_columns()),
+        }
+    }
+
+    /// Move the top-level `columns` shorthand into `style`
+    fn fold_columns(mut self) -> Self {
+        if let Some(columns) = self.columns.take() {
+            self.style.columns = Some(columns);
+        }
+        self
+    }
+
+    /// Resolve `date: today` (or `yesterday`, `today+7d`, ...) and `auto_date`
+    /// against the build date, and check that `date_format` and `lang` are usable
+    ///
+    /// `today` is `None` in a reproducible build without `SOURCE_DATE_EPOCH`,
+    /// where a date relative to it is an error.
+    pub fn resolve_date(&mut self, today: Option<NaiveDate>) -> Result<()> {
+        let relative = match &self.date {
+            Some(value) => date::resolve_relative(value, NaiveDate::default()).is_some(),
+            None => self.auto_date,
         };
+        if relative {
+            let today = today.ok_or_else(|| {
+                Md2PdfError::InvalidInput(
+                    "the date depends on the day of the build; set SOURCE_DATE_EPOCH for a reproducible build"
+                        .to_string(),
+                )
+            })?;
+            let resolved = match &self.date {
+                Some(value) => date::resolve_relative(value, today),
+                None => Some(today),
+            };
+            if let Some(resolved) = resolved {
+                self.date = Some(resolved.format("%Y-%m-%d").to_string());
+            }
+        }
 
-        let yaml_content = &after_first_delimiter[..end_pos].trim();
-        let remaining = &after_first_delimiter[end_pos + 4..].trim_start();
+        if let Some(format) = &self.date_format {
+            let sample = NaiveDate::default()
+                .and_hms_opt(0, 0, 0)
+                .unwrap_or_default();
+            if date::format_date(sample, format, None).is_none() {
+                return Err(Md2PdfError::InvalidInput(format!(
+                    "invalid date_format `{}`",
+                    format
+                )));
+            }
+        }
+
+        if let Some(lang) = &self.lang {
+            if split_lang(lang).is_none() {
+                return Err(Md2PdfError::InvalidInput(format!(
+                    "invalid lang `{lang}`; expected a language code with an optional region, such as `fr` or `pt-BR`"
+                )));
+            }
+        }
+
+        Ok(())
+    }
 
-        let frontmatter: Frontmatter = serde_yaml::from_str(yaml_content)?;
-        Ok((frontmatter, remaining))
+    /// The date as it should be displayed, honouring `date_format` and `lang`
+    pub fn display_date(&self) -> Option<String> {
+        let date = self.date.as_ref()?;
+        let formatted = self.date_format.as_ref().and_then(|format| {
+            date::format_date(date::parse_date(date)?, format, self.lang.as_deref())
+        });
+        Some(formatted.unwrap_or_else(|| date.clone()))
+    }
+
+    /// Variables for `{{ ... }}` substitution: the known fields plus `extra`
+    pub fn variables(&self) -> Variables {
+        let mut vars = Variables::default();
+
+        for (key, value) in [
+            ("title", &self.title),
+            ("author", &self.author),
+            ("date", &self.date),
+            ("abstract_text", &self.abstract_text),
+        ] {
+            if let Some(value) = value {
+                vars.insert(key, Value::String(value.clone()));
+            }
+        }
+
+        if !self.keywords.is_empty() {
+            let keywords = self.keywords.iter().cloned().map(Value::String).collect();
+            vars.insert("keywords", Value::Sequence(keywords));
+        }
+
+        for (key, value) in &self.extra {
+            vars.insert(key, value.clone());
+        }
+
+        vars.set_date_style(self.date_format.clone(), self.lang.clone());
+        vars
+    }
+
+    /// An `extra` key with a string, number or boolean value, as text
+    pub fn extra_text(&self, key: &str) -> Option<String> {
+        match self.extra.get(key)? {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            _ => None,
+        }
     }
 
     pub fn to_typst_header(&self) -> String {
@@ -50,10 +269,10 @@ impl Frontmatter {
             ));
         }
 
-        if let Some(date) = &self.date {
+        if let Some(date) = self.display_date() {
             parts.push(format!(
                 r#"#align(center, text(size: 11pt, style: "italic")[{}])"#,
-                escape_typst(date)
+                escape_typst(&date)
             ));
         }
 
@@ -65,6 +284,123 @@ impl Frontmatter {
     }
 }
 
+/// Split a language tag such as `fr`, `pt-BR` or `pt_BR` into a lowercase
+/// ISO 639 language code and an uppercase ISO 3166 region code
+pub fn split_lang(lang: &str) -> Option<(String, Option<String>)> {
+    let lang = lang.trim().replace('_', "-");
+    let (language, region) = match lang.split_once('-') {
+        Some((language, region)) => (language, Some(region)),
+        None => (lang.as_str(), None),
+    };
+    let letters = |code: &str, lengths: &[usize]| {
+        lengths.contains(&code.len()) && code.chars().all(|c| c.is_ascii_alphabetic())
+    };
+    if !letters(language, &[2, 3]) || region.is_some_and(|region| !letters(region, &[2])) {
+        return None;
+    }
+    Some((
+        language.to_lowercase(),
+        region.map(|region| region.to_uppercase()),
+    ))
+}
+
+/// Split `content` at the first line consisting only of one of `delimiters`
+fn split_at_delimiter<'a>(content: &'a str, delimiters: &[&str]) -> Option<(&'a str, &'a str)> {
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        if delimiters.contains(&line.trim_end()) {
+            return Some((&content[..offset], &content[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// Drop whole blank lines, keeping the indentation of the first content line
+fn skip_blank_lines(mut content: &str) -> &str {
+    while let Some((line, rest)) = content.split_once('\n') {
+        if !line.trim().is_empty() {
+            break;
+        }
+        content = rest;
+    }
+    content
+}
+
+/// Turn a parse error into [`Md2PdfError::Frontmatter`] with a line number in
+/// the original file, given the line on which `text` starts
+fn locate(error: Md2PdfError, text: &str, first_line: usize) -> Md2PdfError {
+    let (line, message) = match &error {
+        Md2PdfError::Yaml(e) => match e.location() {
+            Some(location) => (location.line(), strip_location(&e.to_string())),
+            None => return error,
+        },
+        Md2PdfError::Toml(e) => match e.span() {
+            Some(span) => (
+                text[..span.start].matches('\n').count() + 1,
+                e.message().to_string(),
+            ),
+            None => return error,
+        },
+        Md2PdfError::Json(e) => (e.line(), strip_location(&e.to_string())),
+        _ => return error,
+    };
+
+    Md2PdfError::Frontmatter {
+        line: first_line + line - 1,
+        message,
+    }
+}
+
+/// Remove the parser's own " at line X column Y" suffix
+fn strip_location(message: &str) -> String {
+    message
+        .rfind(" at line ")
+        .map_or(message, |pos| &message[..pos])
+        .to_string()
+}
+
+/// A JSON object starts with `{` followed by a key or `}`, unlike a `{{ variable }}`
+fn is_json_object_start(content: &str) -> bool {
+    content
+        .strip_prefix('{')
+        .map(str::trim_start)
+        .is_some_and(|rest| rest.starts_with('"') || rest.starts_with('}'))
+}
+
+fn toml_to_yaml(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::Number(i.into()),
+        toml::Value::Float(f) => Value::Number(f.into()),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(items) => Value::Sequence(items.into_iter().map(toml_to_yaml).collect()),
+        toml::Value::Table(table) => Value::Mapping(
+            table
+                .into_iter()
+                .map(|(k, v)| (Value::String(k), toml_to_yaml(v)))
+                .collect(),
+        ),
+    }
+}
+
+fn merge_values(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
 fn escape_typst(s: &str) -> String {
     s.replace('\\', "\\\\")
         .replace('#', "\\#")
@@ -101,4 +437,130 @@ date: 2025-01-21
         assert_eq!(fm.date, Some("2025-01-21".to_string()));
         assert!(remaining.starts_with("# Hello"));
     }
+
+    #[test]
+    fn test_extra_fields_become_variables() {
+        let content = "---\ntitle: Guide\nversion: 2.3.1\n---\nBody";
+        let (fm, _) = Frontmatter::extract(content).unwrap();
+        let vars = fm.variables();
+        assert_eq!(vars.get("version"), Some(&Value::String("2.3.1".into())));
+        assert_eq!(vars.get("title"), Some(&Value::String("Guide".into())));
+    }
+
+    #[test]
+    fn test_today_and_date_format() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 5).unwrap();
+
+        let (mut fm, _) =
+            Frontmatter::extract("---\ndate: today\ndate_format: \"%-d %B %Y\"\nlang: fr\n---\n")
+                .unwrap();
+        fm.resolve_date(Some(today)).unwrap();
+        assert_eq!(fm.date, Some("2025-03-05".to_string()));
+        assert_eq!(fm.display_date(), Some("5 mars 2025".to_string()));
+
+        let (mut fm, _) = Frontmatter::extract("---\nauto_date: true\n---\n").unwrap();
+        fm.resolve_date(Some(today)).unwrap();
+        assert_eq!(fm.display_date(), Some("2025-03-05".to_string()));
+
+        // Without a build date, only relative dates are an error
+        let (mut fm, _) = Frontmatter::extract("---\ndate: today+1d\n---\n").unwrap();
+        let err = fm.resolve_date(None).unwrap_err();
+        assert!(err.to_string().contains("SOURCE_DATE_EPOCH"), "{err}");
+        let (mut fm, _) = Frontmatter::extract("---\ndate: 2025-03-05\n---\n").unwrap();
+        fm.resolve_date(None).unwrap();
+    }
+
+    #[test]
+    fn test_lang_must_be_a_language_tag() {
+        assert_eq!(
+            split_lang("pt_br"),
+            Some(("pt".to_string(), Some("BR".to_string())))
+        );
+        assert_eq!(split_lang("FR"), Some(("fr".to_string(), None)));
+
+        let today = NaiveDate::from_ymd_opt(2025, 3, 5).unwrap();
+        for lang in ["english", "zh-Hant-TW", "en-"] {
+            let mut fm = Frontmatter {
+                lang: Some(lang.to_string()),
+                ..Frontmatter::default()
+            };
+            let err = fm.resolve_date(Some(today)).unwrap_err();
+            assert!(matches!(err, Md2PdfError::InvalidInput(_)), "{err}");
+            assert!(err.to_string().contains(&format!("`{lang}`")), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_toml_frontmatter() {
+        let content = "+++\ntitle = \"Hugo Page\"\ndate = 2025-01-21\n+++\n\n# Body";
+        let (fm, remaining) = Frontmatter::extract(content).unwrap();
+        assert_eq!(fm.title, Some("Hugo Page".to_string()));
+        assert_eq!(fm.date, Some("2025-01-21".to_string()));
+        assert_eq!(remaining, "# Body");
+    }
+
+    #[test]
+    fn test_json_frontmatter() {
+        let content = "{\n  \"title\": \"Generated\",\n  \"keywords\": [\"a\"]\n}\n\n# Body";
+        let (fm, remaining) = Frontmatter::extract(content).unwrap();
+        assert_eq!(fm.title, Some("Generated".to_string()));
+        assert_eq!(fm.keywords, vec!["a".to_string()]);
+        assert_eq!(remaining, "# Body");
+
+        let (fm, remaining) = Frontmatter::extract("{{ title }}").unwrap();
+        assert!(fm.title.is_none());
+        assert_eq!(remaining, "{{ title }}");
+    }
+
+    #[test]
+    fn test_delimiters_must_be_own_lines() {
+        let content = "---\ntitle: A\nnote: ---text\n...\nBody\n";
+        let (fm, remaining) = Frontmatter::extract(content).unwrap();
+        assert_eq!(fm.title, Some("A".to_string()));
+        assert_eq!(fm.extra.get("note"), Some(&Value::String("---text".into())));
+        assert_eq!(remaining, "Body\n");
+
+        let content = "+++\nnote = \"\"\"\n+++text\n++++\n\"\"\"\n+++\n\nBody";
+        let (fm, remaining) = Frontmatter::extract(content).unwrap();
+        assert_eq!(
+            fm.extra.get("note"),
+            Some(&Value::String("+++text\n++++\n".into()))
+        );
+        assert_eq!(remaining, "Body");
+    }
+
+    #[test]
+    fn test_leading_content_is_not_frontmatter() {
+        let content = "\n---\ntitle: A\n---\n";
+        let (fm, remaining) = Frontmatter::extract(content).unwrap();
+        assert!(fm.title.is_none());
+        assert_eq!(remaining, content);
+    }
+
+    #[test]
+    fn test_error_reports_original_line() {
+        let content = "---\ntitle: A\nauthor: [unclosed\n---\n";
+        let err = Frontmatter::extract(content).unwrap_err();
+        assert!(
+            matches!(err, Md2PdfError::Frontmatter { line: 3, .. }),
+            "{err}"
+        );
+
+        let content = "+++\ntitle = \"A\"\nauthor = \n+++\n";
+        let err = Frontmatter::extract(content).unwrap_err();
+        assert!(
+            matches!(err, Md2PdfError::Frontmatter { line: 3, .. }),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_merge_overlay() {
+        let (mut fm, _) = Frontmatter::extract("---\ntitle: Doc\nversion: 1\n---\n").unwrap();
+        let overlay: Mapping = serde_yaml::from_str("version: 2\nauthor: Ops").unwrap();
+        fm.merge(&overlay).unwrap();
+        assert_eq!(fm.title, Some("Doc".to_string()));
+        assert_eq!(fm.author, Some("Ops".to_string()));
+        assert_eq!(fm.extra.get("version"), Some(&Value::Number(2.into())));
+    }
 }
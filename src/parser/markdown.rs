@@ -2,12 +2,15 @@ use pulldown_cmark::{Event, Options, Parser};
 
 /// Parse markdown content and return an iterator of events
 pub fn parse_markdown(content: &str) -> Vec<Event<'_>> {
-    let options = Options::ENABLE_TABLES
+    Parser::new_ext(content, options()).collect()
+}
+
+/// The Markdown extensions enabled when parsing
+pub(crate) fn options() -> Options {
+    Options::ENABLE_TABLES
         | Options::ENABLE_STRIKETHROUGH
         | Options::ENABLE_TASKLISTS
-        | Options::ENABLE_MATH;
-
-    Parser::new_ext(content, options).collect()
+        | Options::ENABLE_MATH
 }
 
 #[cfg(test)]
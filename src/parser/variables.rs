@@ -0,0 +1,363 @@
+use crate::parser::markdown;
+use crate::utils::date;
+use crate::{Md2PdfError, Result};
+use pulldown_cmark::{Event, Parser, Tag};
+use serde_yaml::{Mapping, Value};
+use std::ops::Range;
+
+/// Values available to `{{ ... }}` expressions
+#[derive(Debug, Clone, Default)]
+pub struct Variables {
+    values: Mapping,
+    date_format: Option<String>,
+    lang: Option<String>,
+}
+
+impl Variables {
+    pub fn new(values: Mapping) -> Self {
+        Self {
+            values,
+            ..Default::default()
+        }
+    }
+
+    pub fn insert(&mut self, key: &str, value: Value) {
+        self.values.insert(Value::String(key.to_string()), value);
+    }
+
+    /// Display date values with `date_format`, and localize names for `lang`
+    pub fn set_date_style(&mut self, date_format: Option<String>, lang: Option<String>) {
+        self.date_format = date_format;
+        self.lang = lang;
+    }
+
+    /// Look up a variable, following dots into nested mappings
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        let mut parts = path.split('.');
+        let mut value = self.values.get(parts.next()?)?;
+        for part in parts {
+            value = value.as_mapping()?.get(part)?;
+        }
+        Some(value)
+    }
+
+    fn render(&self, value: &Value, style_dates: bool) -> std::result::Result<String, String> {
+        match value {
+            Value::Null => Ok(String::new()),
+            Value::Bool(b) => Ok(b.to_string()),
+            Value::Number(n) => Ok(n.to_string()),
+            Value::String(s) if style_dates => Ok(self.render_date(s).unwrap_or_else(|| s.clone())),
+            Value::String(s) => Ok(s.clone()),
+            Value::Sequence(items) => Ok(items
+                .iter()
+                .map(|item| self.render(item, style_dates))
+                .collect::<std::result::Result<Vec<_>, _>>()?
+                .join(", ")),
+            Value::Mapping(_) => Err("cannot insert a mapping; select one of its keys".to_string()),
+            Value::Tagged(tagged) => self.render(&tagged.value, style_dates),
+        }
+    }
+
+    /// Apply `date_format` to values that parse as dates
+    fn render_date(&self, value: &str) -> Option<String> {
+        let format = self.date_format.as_deref()?;
+        date::format_date(date::parse_date(value)?, format, self.lang.as_deref())
+    }
+}
+
+/// Replace `{{ name }}` and `{{ name | filter(...) }}` expressions in Markdown
+///
+/// Values are inserted as Markdown. `\{{` produces a literal `{{`, `{{#...}}`
+/// is left untouched, and code spans and code blocks of every kind, including
+/// those in lists and block quotes, are copied verbatim.
+/// `first_line` is the line number of `input` in the original file, for errors.
+pub fn substitute(input: &str, vars: &Variables, first_line: usize) -> Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut offset = 0;
+
+    for code in code_ranges(input) {
+        if code.start < offset {
+            continue;
+        }
+        let line = first_line + input[..offset].matches('\n').count();
+        substitute_text(&input[offset..code.start], vars, line, &mut output)?;
+        output.push_str(&input[code.clone()]);
+        offset = code.end;
+    }
+
+    let line = first_line + input[..offset].matches('\n').count();
+    substitute_text(&input[offset..], vars, line, &mut output)?;
+    Ok(output)
+}
+
+/// Byte ranges of the code spans and code blocks in `input`, in order
+fn code_ranges(input: &str) -> Vec<Range<usize>> {
+    Parser::new_ext(input, markdown::options())
+        .into_offset_iter()
+        .filter(|(event, _)| matches!(event, Event::Code(_) | Event::Start(Tag::CodeBlock(_))))
+        .map(|(_, range)| range)
+        .collect()
+}
+
+fn substitute_text(
+    text: &str,
+    vars: &Variables,
+    first_line: usize,
+    output: &mut String,
+) -> Result<()> {
+    for (index, line) in text.split_inclusive('\n').enumerate() {
+        substitute_line(line, vars, first_line + index, output)?;
+    }
+    Ok(())
+}
+
+fn substitute_line(
+    line: &str,
+    vars: &Variables,
+    line_no: usize,
+    output: &mut String,
+) -> Result<()> {
+    let mut rest = line;
+
+    while let Some(pos) = rest.find(['\\', '{']) {
+        output.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if rest.starts_with("\\{{") {
+            output.push_str("{{");
+            rest = &rest[3..];
+        } else if rest.starts_with("{{") && !rest.starts_with("{{#") {
+            let Some(end) = rest.find("}}") else {
+                output.push_str(rest);
+                return Ok(());
+            };
+            let value = evaluate(&rest[2..end], vars).map_err(|message| Md2PdfError::Template {
+                line: line_no,
+                message,
+            })?;
+            output.push_str(&value);
+            rest = &rest[end + 2..];
+        } else {
+            let ch = rest.chars().next().unwrap_or_default();
+            output.push(ch);
+            rest = &rest[ch.len_utf8()..];
+        }
+    }
+
+    output.push_str(rest);
+    Ok(())
+}
+
+fn evaluate(expr: &str, vars: &Variables) -> std::result::Result<String, String> {
+    evaluate_optional(expr, vars)?.ok_or_else(|| {
+        let name = split_pipes(expr)[0].trim();
+        format!("undefined variable `{}`", name)
+    })
+}
+
+/// Evaluate `name | filter(...) | ...`, giving `None` for an undefined
+/// variable that no filter supplies a value for
+pub(crate) fn evaluate_optional(
+    expr: &str,
+    vars: &Variables,
+) -> std::result::Result<Option<String>, String> {
+    let mut stages = split_pipes(expr).into_iter();
+    let name = stages.next().unwrap_or_default().trim();
+
+    if name.is_empty()
+        || !name.split('.').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        })
+    {
+        return Err(format!("invalid variable name `{}`", name));
+    }
+
+    let filters = stages
+        .map(|stage| parse_filter(stage.trim()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    // An explicit `format` filter replaces the document's `date_format`
+    let style_dates = !filters.iter().any(|(filter, _)| *filter == "format");
+    let mut value = vars
+        .get(name)
+        .map(|v| vars.render(v, style_dates))
+        .transpose()?;
+
+    for (filter, args) in filters {
+        value = apply_filter(filter, &args, value, vars)?;
+    }
+
+    Ok(value)
+}
+
+/// Split on `|` outside of quoted filter arguments
+fn split_pipes(expr: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+
+    for (i, c) in expr.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '|') => {
+                parts.push(&expr[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    parts.push(&expr[start..]);
+    parts
+}
+
+fn parse_filter(stage: &str) -> std::result::Result<(&str, Vec<String>), String> {
+    let Some(open) = stage.find('(') else {
+        return Ok((stage, Vec::new()));
+    };
+
+    let name = stage[..open].trim();
+    let Some(inner) = stage[open + 1..].strip_suffix(')') else {
+        return Err(format!("missing `)` in filter `{}`", stage));
+    };
+
+    let mut args = Vec::new();
+    let mut rest = inner.trim();
+    while !rest.is_empty() {
+        let quote = rest.chars().next().unwrap_or_default();
+        if quote != '"' && quote != '\'' {
+            return Err(format!(
+                "filter arguments must be quoted strings in `{}`",
+                stage
+            ));
+        }
+        let Some(end) = rest[1..].find(quote) else {
+            return Err(format!("unterminated string in filter `{}`", stage));
+        };
+        args.push(rest[1..end + 1].to_string());
+        rest = rest[end + 2..].trim_start();
+        rest = rest.strip_prefix(',').unwrap_or(rest).trim_start();
+    }
+
+    Ok((name, args))
+}
+
+fn apply_filter(
+    filter: &str,
+    args: &[String],
+    value: Option<String>,
+    vars: &Variables,
+) -> std::result::Result<Option<String>, String> {
+    let expect_args = |count: usize| {
+        if args.len() == count {
+            Ok(())
+        } else {
+            Err(format!("filter `{}` takes {} argument(s)", filter, count))
+        }
+    };
+
+    match filter {
+        "default" => {
+            expect_args(1)?;
+            Ok(Some(value.unwrap_or_else(|| args[0].clone())))
+        }
+        "upper" => {
+            expect_args(0)?;
+            Ok(value.map(|v| v.to_uppercase()))
+        }
+        "lower" => {
+            expect_args(0)?;
+            Ok(value.map(|v| v.to_lowercase()))
+        }
+        "format" => {
+            expect_args(1)?;
+            value
+                .map(|v| {
+                    let datetime = date::parse_date(&v)
+                        .ok_or_else(|| format!("`format` expects a date, got `{}`", v))?;
+                    date::format_date(datetime, &args[0], vars.lang.as_deref())
+                        .ok_or_else(|| format!("invalid date format `{}`", args[0]))
+                })
+                .transpose()
+        }
+        _ => Err(format!("unknown filter `{}`", filter)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(yaml: &str) -> Variables {
+        Variables::new(serde_yaml::from_str(yaml).unwrap())
+    }
+
+    #[test]
+    fn test_substitute_variable() {
+        let v = vars("version: 2.3.1\nmeta:\n  team: Docs");
+        let out = substitute("Version {{ version }} by {{meta.team}}", &v, 1).unwrap();
+        assert_eq!(out, "Version 2.3.1 by Docs");
+    }
+
+    #[test]
+    fn test_date_format_filter() {
+        let v = vars("date: 2025-01-21");
+        let out = substitute(r#"{{ date | format("%B %Y") }}"#, &v, 1).unwrap();
+        assert_eq!(out, "January 2025");
+    }
+
+    #[test]
+    fn test_date_style() {
+        let mut v = vars("date: 2025-03-05\nversion: 2.3.1");
+        v.set_date_style(Some("%-d %B %Y".to_string()), Some("fr".to_string()));
+        let out = substitute(
+            r#"{{ date }}, {{ date | format("%B") }}, {{ version }}"#,
+            &v,
+            1,
+        );
+        assert_eq!(out.unwrap(), "5 mars 2025, mars, 2.3.1");
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let err = substitute("ok\n{{ missing }}", &Variables::default(), 5).unwrap_err();
+        assert!(matches!(err, Md2PdfError::Template { line: 6, .. }));
+
+        let out = substitute(
+            r#"{{ missing | default("n/a") }}"#,
+            &Variables::default(),
+            1,
+        )
+        .unwrap();
+        assert_eq!(out, "n/a");
+    }
+
+    #[test]
+    fn test_escapes_and_code_are_untouched() {
+        let v = Variables::default();
+        let md = "\\{{ name }} and `{{ name }}` and {{#include a.rs}}\n```\n{{ name }}\n```\n";
+        let out = substitute(md, &v, 1).unwrap();
+        assert_eq!(
+            out,
+            "{{ name }} and `{{ name }}` and {{#include a.rs}}\n```\n{{ name }}\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_nested_and_indented_code_is_untouched() {
+        let v = vars("name: x");
+        let md = "{{ name }}\n\n    {{ indented }}\n\n- item\n\n  ```\n  {{ listed }}\n  ```\n\n> ~~~\n> {{ quoted }}\n> ~~~\n\n`a\n{{ span }}` {{ name }}\n";
+        let out = substitute(md, &v, 1).unwrap();
+        assert_eq!(
+            out,
+            "x\n\n    {{ indented }}\n\n- item\n\n  ```\n  {{ listed }}\n  ```\n\n> ~~~\n> {{ quoted }}\n> ~~~\n\n`a\n{{ span }}` x\n"
+        );
+
+        let err = substitute("```\nok\n```\n{{ missing }}", &v, 10).unwrap_err();
+        assert!(matches!(err, Md2PdfError::Template { line: 13, .. }));
+    }
+}
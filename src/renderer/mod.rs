@@ -1 +1,168 @@
 pub mod pdf;
+pub mod png;
+pub mod svg;
+mod world;
+
+use crate::config::{Config, OutputFormat};
+use crate::{Md2PdfError, Result};
+use pdf::Attachment;
+use std::fs;
+use std::path::{Path, PathBuf};
+use typst::layout::{Page, PagedDocument};
+use world::Md2PdfWorld;
+
+/// Rendered output in one of the supported formats
+#[derive(Debug, Clone)]
+pub enum Rendered {
+    /// A complete PDF file
+    Document(Vec<u8>),
+    /// One PNG or SVG file per page, with its 1-based page number
+    Pages(Vec<(usize, Vec<u8>)>),
+}
+
+impl Rendered {
+    /// Write the output and return the paths written
+    ///
+    /// For pages, `{n}` in `output` is replaced by the page number. Without
+    /// `{n}`, a single page is written to `output` as is and several pages get
+    /// `-{n}` before the extension.
+    pub fn write(&self, output: &Path) -> Result<Vec<PathBuf>> {
+        match self {
+            Self::Document(data) => {
+                fs::write(output, data)?;
+                Ok(vec![output.to_path_buf()])
+            }
+            Self::Pages(pages) => pages
+                .iter()
+                .map(|(number, data)| {
+                    let path = page_path(output, *number, pages.len() == 1);
+                    fs::write(&path, data)?;
+                    Ok(path)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Render Typst code in `config.format`; attachments only apply to PDF
+///
+/// `markdown` is the source the code was generated from, if any, so that
+/// PDF standard errors can point at its lines.
+pub fn render(
+    typst_code: &str,
+    markdown: Option<&str>,
+    config: &Config,
+    attachments: &[Attachment],
+) -> Result<Rendered> {
+    Session::new(config).render(typst_code, markdown, config, attachments)
+}
+
+/// Renders successive versions of a document with one Typst world
+///
+/// Only the main source changes between renders, so Typst's caches carry
+/// over and unchanged content is not laid out again.
+pub struct Session {
+    world: Md2PdfWorld,
+}
+
+impl Session {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            world: Md2PdfWorld::new(String::new(), config),
+        }
+    }
+
+    pub fn render(
+        &mut self,
+        typst_code: &str,
+        markdown: Option<&str>,
+        config: &Config,
+        attachments: &[Attachment],
+    ) -> Result<Rendered> {
+        let source = match config.format {
+            OutputFormat::Pdf => pdf::attach(typst_code, attachments, config, &mut self.world)?,
+            OutputFormat::Png | OutputFormat::Svg => typst_code.to_string(),
+        };
+        self.world.set_main(&source);
+
+        let rendered = self.world.compile().and_then(|doc| match config.format {
+            OutputFormat::Pdf => {
+                pdf::export(&self.world, &doc, typst_code, markdown, config).map(Rendered::Document)
+            }
+            OutputFormat::Png => png::export(&doc, config).map(Rendered::Pages),
+            OutputFormat::Svg => svg::export(&doc, config).map(Rendered::Pages),
+        });
+
+        // Drop cached results that went unused for several renders
+        comemo::evict(10);
+        rendered
+    }
+
+    /// Files read from disk by the last render, such as images
+    pub fn dependencies(&self) -> Vec<PathBuf> {
+        self.world.dependencies()
+    }
+}
+
+/// The pages chosen by `config.pages`, with their 1-based numbers
+fn selected_pages<'a>(doc: &'a PagedDocument, config: &Config) -> Result<Vec<(usize, &'a Page)>> {
+    let pages: Vec<_> = doc
+        .pages
+        .iter()
+        .enumerate()
+        .map(|(index, page)| (index + 1, page))
+        .filter(|(number, _)| {
+            config.pages.is_empty() || config.pages.iter().any(|range| range.contains(*number))
+        })
+        .collect();
+
+    if pages.is_empty() {
+        return Err(Md2PdfError::InvalidInput(format!(
+            "no pages selected; the document has {} page(s)",
+            doc.pages.len()
+        )));
+    }
+    Ok(pages)
+}
+
+/// Output path for one page of a multi-file export
+fn page_path(pattern: &Path, number: usize, single: bool) -> PathBuf {
+    let text = pattern.to_string_lossy();
+    if text.contains("{n}") {
+        return PathBuf::from(text.replace("{n}", &number.to_string()));
+    }
+    if single {
+        return pattern.to_path_buf();
+    }
+
+    let stem = pattern
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match pattern.extension() {
+        Some(ext) => format!("{stem}-{number}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{number}"),
+    };
+    pattern.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_path() {
+        assert_eq!(
+            page_path(Path::new("out/page-{n}.png"), 3, false),
+            PathBuf::from("out/page-3.png")
+        );
+        assert_eq!(
+            page_path(Path::new("out/doc.svg"), 2, false),
+            PathBuf::from("out/doc-2.svg")
+        );
+        assert_eq!(
+            page_path(Path::new("doc.png"), 1, true),
+            PathBuf::from("doc.png")
+        );
+    }
+}
@@ -1,199 +1,239 @@
-use crate::config::Config;
+use super::world::Md2PdfWorld;
+use crate::config::{Config, PageRange, PdfStandard};
+use crate::transpiler::typst::escape_typst_string;
+use crate::utils::date;
 use crate::{Md2PdfError, Result};
-use std::collections::HashMap;
-use std::sync::OnceLock;
-use typst::diag::{FileError, FileResult, Severity};
-use typst::foundations::{Bytes, Datetime};
-use typst::syntax::{FileId, Source, VirtualPath};
-use typst::text::{Font, FontBook};
-use typst::utils::LazyHash;
-use typst::{Library, LibraryExt};
-
-static FONTS: OnceLock<(LazyHash<FontBook>, Vec<Font>)> = OnceLock::new();
-
-pub fn render_pdf(typst_code: &str, _config: &Config) -> Result<Vec<u8>> {
-    let world = Md2PdfWorld::new(typst_code.to_string());
-
-    let result = typst::compile(&world);
-
-    match result.output {
-        Ok(doc) => {
-            let options = typst_pdf::PdfOptions::default();
-            match typst_pdf::pdf(&doc, &options) {
-                Ok(pdf) => Ok(pdf),
-                Err(errors) => {
-                    let error_messages: Vec<String> =
-                        errors.iter().map(|e| format!("{:?}", e)).collect();
-                    Err(Md2PdfError::Typst(error_messages.join("\n")))
-                }
-            }
-        }
-        Err(errors) => {
-            let error_messages: Vec<String> = errors
-                .iter()
-                .map(|e| {
-                    let severity = match e.severity {
-                        Severity::Error => "error",
-                        Severity::Warning => "warning",
-                    };
-                    format!("{}: {}", severity, e.message)
-                })
-                .collect();
-            Err(Md2PdfError::Typst(error_messages.join("\n")))
-        }
-    }
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use std::num::NonZeroUsize;
+use typst::diag::SourceDiagnostic;
+use typst::foundations::{Datetime, Smart};
+use typst::layout::{PageRanges, PagedDocument};
+use typst_pdf::{PdfStandards, Timestamp};
+
+/// A file embedded in the PDF
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    /// File name shown by PDF viewers
+    pub name: String,
+    pub data: Vec<u8>,
+    pub mime_type: String,
+    pub description: String,
+    /// How the file relates to the document: `source`, `data` or `supplement`
+    pub relationship: &'static str,
 }
 
-struct Md2PdfWorld {
-    library: LazyHash<Library>,
-    book: LazyHash<FontBook>,
-    fonts: Vec<Font>,
-    main: Source,
-    files: HashMap<FileId, Source>,
+/// Render Typst code to PDF; `markdown` is the source it was generated from,
+/// if any, for errors to point at
+pub fn render_pdf(
+    typst_code: &str,
+    markdown: Option<&str>,
+    config: &Config,
+    attachments: &[Attachment],
+) -> Result<Vec<u8>> {
+    let mut world = Md2PdfWorld::new(String::new(), config);
+    let source = attach(typst_code, attachments, config, &mut world)?;
+    world.set_main(&source);
+    let doc = world.compile()?;
+    export(&world, &doc, typst_code, markdown, config)
 }
 
-impl Md2PdfWorld {
-    fn new(source: String) -> Self {
-        let (book, fonts) = FONTS
-            .get_or_init(|| {
-                let mut book = FontBook::new();
-                let mut fonts = Vec::new();
-
-                // Load embedded fonts from typst-assets
-                for data in typst_assets::fonts() {
-                    let buffer = Bytes::new(data);
-                    for font in Font::iter(buffer) {
-                        book.push(font.info().clone());
-                        fonts.push(font);
-                    }
-                }
-
-                // Load system fonts as additional options
-                #[cfg(target_os = "macos")]
-                {
-                    for entry in std::fs::read_dir("/System/Library/Fonts")
-                        .into_iter()
-                        .flatten()
-                        .flatten()
-                    {
-                        Self::load_font_file(&entry.path(), &mut book, &mut fonts);
-                    }
-
-                    if let Some(home) = std::env::var_os("HOME") {
-                        let user_fonts = std::path::Path::new(&home).join("Library/Fonts");
-                        for entry in std::fs::read_dir(user_fonts)
-                            .into_iter()
-                            .flatten()
-                            .flatten()
-                        {
-                            Self::load_font_file(&entry.path(), &mut book, &mut fonts);
-                        }
-                    }
-                }
-
-                #[cfg(target_os = "linux")]
-                {
-                    for dir in ["/usr/share/fonts", "/usr/local/share/fonts"] {
-                        Self::load_fonts_recursive(
-                            std::path::Path::new(dir),
-                            &mut book,
-                            &mut fonts,
-                        );
-                    }
-
-                    if let Some(home) = std::env::var_os("HOME") {
-                        let user_fonts = std::path::Path::new(&home).join(".fonts");
-                        Self::load_fonts_recursive(&user_fonts, &mut book, &mut fonts);
-                    }
-                }
-
-                (LazyHash::new(book), fonts)
-            })
-            .clone();
-
-        let main_id = FileId::new(None, VirtualPath::new("main.typ"));
-        let main = Source::new(main_id, source);
+/// Append `pdf.attach` calls to the source and serve the files from the world
+pub(crate) fn attach(
+    typst_code: &str,
+    attachments: &[Attachment],
+    config: &Config,
+    world: &mut Md2PdfWorld,
+) -> Result<String> {
+    if !attachments.is_empty() && config.pdf_standards.contains(&PdfStandard::A2b) {
+        return Err(Md2PdfError::Pdf(
+            "PDF/A-2b does not allow embedded files; use `--pdf-standard a-3b` to embed them"
+                .to_string(),
+        ));
+    }
 
-        Self {
-            library: LazyHash::new(Library::default()),
-            book,
-            fonts,
-            main,
-            files: HashMap::new(),
-        }
+    let mut source = typst_code.to_string();
+    for (index, attachment) in attachments.iter().enumerate() {
+        let path = format!("/.md2pdf/attachments/{index}");
+        source.push_str(&format!(
+            "#pdf.attach(\"{}\", read(\"{}\", encoding: none), relationship: \"{}\", mime-type: \"{}\", description: \"{}\")\n",
+            escape_typst_string(&attachment.name),
+            path,
+            attachment.relationship,
+            escape_typst_string(&attachment.mime_type),
+            escape_typst_string(&attachment.description),
+        ));
+        world.add_file(&path, attachment.data.clone());
     }
+    Ok(source)
+}
 
-    fn load_font_file(path: &std::path::Path, book: &mut FontBook, fonts: &mut Vec<Font>) {
-        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
-        if !matches!(ext.to_lowercase().as_str(), "ttf" | "otf" | "ttc" | "otc") {
-            return;
-        }
+/// Export a compiled document; `typst_code` identifies it in reproducible
+/// builds, and errors point at lines of `markdown`
+pub(crate) fn export(
+    world: &Md2PdfWorld,
+    doc: &PagedDocument,
+    typst_code: &str,
+    markdown: Option<&str>,
+    config: &Config,
+) -> Result<Vec<u8>> {
+    // A content hash keeps the document ID stable across builds
+    let ident = config
+        .reproducible
+        .then(|| format!("{:032x}", typst::utils::hash128(typst_code)));
+    // Left out of a reproducible build without SOURCE_DATE_EPOCH
+    let timestamp = date::build_time(config.reproducible).and_then(pdf_timestamp);
+    let archival = config
+        .pdf_standards
+        .iter()
+        .any(|standard| matches!(standard, PdfStandard::A2b | PdfStandard::A3b));
+    if timestamp.is_none() && archival {
+        return Err(Md2PdfError::Pdf(
+            "PDF/A needs a creation date; set SOURCE_DATE_EPOCH for a reproducible build"
+                .to_string(),
+        ));
+    }
+    let options = typst_pdf::PdfOptions {
+        ident: ident.as_deref().map_or(Smart::Auto, Smart::Custom),
+        timestamp,
+        page_ranges: page_ranges(&config.pages),
+        standards: pdf_standards(&config.pdf_standards)?,
+        ..Default::default()
+    };
+    typst_pdf::pdf(doc, &options).map_err(|errors| {
+        let error_messages: Vec<String> = errors
+            .iter()
+            .map(|e| describe_export_error(world, e, markdown))
+            .collect();
+        Md2PdfError::Pdf(error_messages.join("\n"))
+    })
+}
 
-        if let Ok(data) = std::fs::read(path) {
-            let buffer = Bytes::new(data);
-            for font in Font::iter(buffer) {
-                book.push(font.info().clone());
-                fonts.push(font);
-            }
-        }
+fn page_ranges(pages: &[PageRange]) -> Option<PageRanges> {
+    if pages.is_empty() {
+        return None;
     }
+    Some(PageRanges::new(
+        pages
+            .iter()
+            .map(|range| {
+                range.start.and_then(NonZeroUsize::new)..=range.end.and_then(NonZeroUsize::new)
+            })
+            .collect(),
+    ))
+}
 
-    #[cfg(target_os = "linux")]
-    fn load_fonts_recursive(path: &std::path::Path, book: &mut FontBook, fonts: &mut Vec<Font>) {
-        if !path.exists() {
-            return;
-        }
+fn pdf_standards(standards: &[PdfStandard]) -> Result<PdfStandards> {
+    let standards: Vec<_> = standards
+        .iter()
+        .map(|standard| match standard {
+            PdfStandard::A2b => typst_pdf::PdfStandard::A_2b,
+            PdfStandard::A3b => typst_pdf::PdfStandard::A_3b,
+            PdfStandard::Ua1 => typst_pdf::PdfStandard::Ua_1,
+        })
+        .collect();
+    PdfStandards::new(&standards).map_err(|e| Md2PdfError::Pdf(e.to_string()))
+}
 
-        for entry in std::fs::read_dir(path).into_iter().flatten() {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if path.is_dir() {
-                    Self::load_fonts_recursive(&path, book, fonts);
-                } else {
-                    Self::load_font_file(&path, book, fonts);
+/// Explain a PDF export (standards validation) error in terms of the Markdown
+///
+/// Diagnostics for metadata and images that the Markdown controls get a hint
+/// on fixing them there in place of Typst's own.
+fn describe_export_error(
+    world: &Md2PdfWorld,
+    error: &SourceDiagnostic,
+    markdown: Option<&str>,
+) -> String {
+    let mut message = error.message.to_string();
+    let hint = if message.ends_with(" missing document title") {
+        Some("add `title:` to the frontmatter or pass `--metadata title=...`".to_string())
+    } else if message.ends_with(" missing document language") {
+        Some("add `lang:` to the frontmatter, such as `lang: en`".to_string())
+    } else if message.ends_with(" missing alt text") {
+        let path = world.snippet(error.span).and_then(first_string_literal);
+        Some(match path {
+            Some(path) => {
+                let line = markdown
+                    .and_then(|markdown| markdown.lines().position(|line| line.contains(path)));
+                match line {
+                    Some(index) => format!(
+                        "describe the image on line {} of the Markdown: `![description]({path})`",
+                        index + 1
+                    ),
+                    None => {
+                        format!("describe the image in the Markdown: `![description]({path})`")
+                    }
                 }
             }
+            None => "give every image a description: `![description](path)`".to_string(),
+        })
+    } else {
+        None
+    };
+
+    match hint {
+        Some(hint) => message.push_str(&format!("\n  hint: {hint}")),
+        None => {
+            for hint in &error.hints {
+                message.push_str(&format!("\n  hint: {hint}"));
+            }
         }
     }
-}
 
-impl typst::World for Md2PdfWorld {
-    fn library(&self) -> &LazyHash<Library> {
-        &self.library
-    }
-
-    fn book(&self) -> &LazyHash<FontBook> {
-        &self.book
-    }
-
-    fn main(&self) -> FileId {
-        self.main.id()
-    }
+    message
+}
 
-    fn source(&self, id: FileId) -> FileResult<Source> {
-        if id == self.main.id() {
-            Ok(self.main.clone())
-        } else if let Some(source) = self.files.get(&id) {
-            Ok(source.clone())
-        } else {
-            Err(FileError::NotFound(id.vpath().as_rooted_path().into()))
-        }
-    }
+/// The contents of the first `"..."` literal in a piece of Typst code
+fn first_string_literal(code: &str) -> Option<&str> {
+    let start = code.find('"')? + 1;
+    let end = code[start..].find('"')?;
+    Some(&code[start..start + end])
+}
 
-    fn file(&self, id: FileId) -> FileResult<Bytes> {
-        Err(FileError::NotFound(id.vpath().as_rooted_path().into()))
-    }
+fn pdf_timestamp(time: DateTime<Utc>) -> Option<Timestamp> {
+    let datetime = Datetime::from_ymd_hms(
+        time.year(),
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+    )?;
+    Some(Timestamp::new_utc(datetime))
+}
 
-    fn font(&self, index: usize) -> Option<Font> {
-        self.fonts.get(index).cloned()
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn today(&self, _offset: Option<i64>) -> Option<Datetime> {
-        let now = chrono::Local::now();
-        Datetime::from_ymd(
-            now.format("%Y").to_string().parse().ok()?,
-            now.format("%m").to_string().parse().ok()?,
-            now.format("%d").to_string().parse().ok()?,
+    #[test]
+    fn test_errors_point_at_the_markdown() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("chart.svg"),
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"10\" height=\"10\"/>",
         )
+        .unwrap();
+        let config = Config {
+            pdf_standards: vec![PdfStandard::Ua1],
+            base_dir: Some(dir.path().to_path_buf()),
+            ..Config::default()
+        };
+
+        let markdown = "---\ntitle: Sales\nlang: en\n---\n\n# Figures\n\n![](chart.svg)\n";
+        let err = crate::convert(markdown, &config).unwrap_err().to_string();
+        assert!(
+            err.contains(
+                "hint: describe the image on line 8 of the Markdown: `![description](chart.svg)`"
+            ),
+            "{err}"
+        );
+
+        // Other diagnostics get no image hint
+        let err = crate::convert("# Figures\n", &config)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("missing document title"), "{err}");
+        assert!(err.contains("add `title:` to the frontmatter"), "{err}");
+        assert!(!err.contains("image"), "{err}");
     }
 }
@@ -0,0 +1,26 @@
+use super::selected_pages;
+use crate::config::Config;
+use crate::{Md2PdfError, Result};
+use typst::layout::PagedDocument;
+
+/// Render the selected pages as PNG images at `config.dpi`
+pub(crate) fn export(doc: &PagedDocument, config: &Config) -> Result<Vec<(usize, Vec<u8>)>> {
+    if !(config.dpi.is_finite() && config.dpi > 0.0) {
+        return Err(Md2PdfError::InvalidInput(format!(
+            "DPI must be a positive number, got {}",
+            config.dpi
+        )));
+    }
+
+    let pixel_per_pt = config.dpi / 72.0;
+
+    selected_pages(doc, config)?
+        .into_iter()
+        .map(|(number, page)| {
+            let png = typst_render::render(page, pixel_per_pt)
+                .encode_png()
+                .map_err(|e| Md2PdfError::Image(e.to_string()))?;
+            Ok((number, png))
+        })
+        .collect()
+}
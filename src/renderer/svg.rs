@@ -0,0 +1,12 @@
+use super::selected_pages;
+use crate::config::Config;
+use crate::Result;
+use typst::layout::PagedDocument;
+
+/// Render the selected pages as standalone SVG documents
+pub(crate) fn export(doc: &PagedDocument, config: &Config) -> Result<Vec<(usize, Vec<u8>)>> {
+    Ok(selected_pages(doc, config)?
+        .into_iter()
+        .map(|(number, page)| (number, typst_svg::svg(page).into_bytes()))
+        .collect())
+}
@@ -0,0 +1,270 @@
+use crate::config::Config;
+use crate::utils::date;
+use crate::{Md2PdfError, Result};
+use chrono::Datelike;
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+use typst::diag::{FileError, FileResult, Severity};
+use typst::foundations::{Bytes, Datetime};
+use typst::layout::PagedDocument;
+use typst::syntax::{FileId, Source, Span, VirtualPath};
+use typst::text::{Font, FontBook};
+use typst::utils::LazyHash;
+use typst::{Library, LibraryExt};
+
+static FONTS: OnceLock<(LazyHash<FontBook>, Vec<Font>)> = OnceLock::new();
+
+pub(crate) struct Md2PdfWorld {
+    library: LazyHash<Library>,
+    book: LazyHash<FontBook>,
+    fonts: Vec<Font>,
+    main: Source,
+    files: HashMap<FileId, Source>,
+    /// In-memory files that take precedence over the file system
+    binaries: HashMap<FileId, Bytes>,
+    /// Directory that relative paths resolve against; no file access without it
+    root: Option<PathBuf>,
+    /// Pin `datetime.today()` even without `SOURCE_DATE_EPOCH`
+    reproducible: bool,
+    /// Files read from disk during the last compilation
+    dependencies: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl Md2PdfWorld {
+    pub(crate) fn new(source: String, config: &Config) -> Self {
+        let (book, fonts) = FONTS
+            .get_or_init(|| {
+                let mut book = FontBook::new();
+                let mut fonts = Vec::new();
+
+                // Load embedded fonts from typst-assets
+                for data in typst_assets::fonts() {
+                    let buffer = Bytes::new(data);
+                    for font in Font::iter(buffer) {
+                        book.push(font.info().clone());
+                        fonts.push(font);
+                    }
+                }
+
+                // Load system fonts as additional options
+                #[cfg(target_os = "macos")]
+                {
+                    for entry in std::fs::read_dir("/System/Library/Fonts")
+                        .into_iter()
+                        .flatten()
+                        .flatten()
+                    {
+                        Self::load_font_file(&entry.path(), &mut book, &mut fonts);
+                    }
+
+                    if let Some(home) = std::env::var_os("HOME") {
+                        let user_fonts = std::path::Path::new(&home).join("Library/Fonts");
+                        for entry in std::fs::read_dir(user_fonts)
+                            .into_iter()
+                            .flatten()
+                            .flatten()
+                        {
+                            Self::load_font_file(&entry.path(), &mut book, &mut fonts);
+                        }
+                    }
+                }
+
+                #[cfg(target_os = "linux")]
+                {
+                    for dir in ["/usr/share/fonts", "/usr/local/share/fonts"] {
+                        Self::load_fonts_recursive(
+                            std::path::Path::new(dir),
+                            &mut book,
+                            &mut fonts,
+                        );
+                    }
+
+                    if let Some(home) = std::env::var_os("HOME") {
+                        let user_fonts = std::path::Path::new(&home).join(".fonts");
+                        Self::load_fonts_recursive(&user_fonts, &mut book, &mut fonts);
+                    }
+                }
+
+                (LazyHash::new(book), fonts)
+            })
+            .clone();
+
+        let main_id = FileId::new(None, VirtualPath::new("main.typ"));
+        let main = Source::new(main_id, source);
+
+        Self {
+            library: LazyHash::new(Library::default()),
+            book,
+            fonts,
+            main,
+            files: HashMap::new(),
+            binaries: HashMap::new(),
+            root: config.base_dir.clone(),
+            reproducible: config.reproducible,
+            dependencies: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// Replace the main source, keeping everything else cached
+    pub(crate) fn set_main(&mut self, text: &str) {
+        self.main.replace(text);
+    }
+
+    /// Files read from disk during the last compilation
+    pub(crate) fn dependencies(&self) -> Vec<PathBuf> {
+        self.dependencies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Serve `data` at the absolute virtual `path`, ahead of the file system
+    pub(crate) fn add_file(&mut self, path: &str, data: Vec<u8>) {
+        let id = FileId::new(None, VirtualPath::new(path));
+        self.binaries.insert(id, Bytes::new(data));
+    }
+
+    /// Compile the main source into laid-out pages
+    pub(crate) fn compile(&self) -> Result<PagedDocument> {
+        self.dependencies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+
+        typst::compile::<PagedDocument>(self)
+            .output
+            .map_err(|errors| {
+                let error_messages: Vec<String> = errors
+                    .iter()
+                    .map(|e| {
+                        let severity = match e.severity {
+                            Severity::Error => "error",
+                            Severity::Warning => "warning",
+                        };
+                        format!("{}: {}", severity, e.message)
+                    })
+                    .collect();
+                Md2PdfError::Typst(error_messages.join("\n"))
+            })
+    }
+
+    /// Map a file ID to a path under the root directory
+    fn resolve(&self, id: FileId) -> FileResult<PathBuf> {
+        let not_found = || FileError::NotFound(id.vpath().as_rooted_path().into());
+        if id.package().is_some() {
+            return Err(not_found());
+        }
+        let root = self.root.as_ref().ok_or_else(|| {
+            FileError::Other(Some(
+                format!(
+                    "cannot read `{}` without a base directory; pass `--base-dir` when reading from stdin",
+                    id.vpath().as_rootless_path().display()
+                )
+                .into(),
+            ))
+        })?;
+        id.vpath().resolve(root).ok_or_else(not_found)
+    }
+
+    fn read(&self, id: FileId) -> FileResult<Vec<u8>> {
+        let path = self.resolve(id)?;
+        let data = std::fs::read(&path).map_err(|e| FileError::from_io(e, &path));
+        self.dependencies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(path);
+        data
+    }
+
+    /// The generated Typst code a span points to
+    pub(crate) fn snippet(&self, span: Span) -> Option<&str> {
+        let range = self.main.range(span)?;
+        self.main.text().get(range)
+    }
+
+    fn load_font_file(path: &std::path::Path, book: &mut FontBook, fonts: &mut Vec<Font>) {
+        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+        if !matches!(ext.to_lowercase().as_str(), "ttf" | "otf" | "ttc" | "otc") {
+            return;
+        }
+
+        if let Ok(data) = std::fs::read(path) {
+            let buffer = Bytes::new(data);
+            for font in Font::iter(buffer) {
+                book.push(font.info().clone());
+                fonts.push(font);
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn load_fonts_recursive(path: &std::path::Path, book: &mut FontBook, fonts: &mut Vec<Font>) {
+        if !path.exists() {
+            return;
+        }
+
+        // Sorted so font indices do not depend on directory order
+        let mut paths: Vec<_> = std::fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path())
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            if path.is_dir() {
+                Self::load_fonts_recursive(&path, book, fonts);
+            } else {
+                Self::load_font_file(&path, book, fonts);
+            }
+        }
+    }
+}
+
+impl typst::World for Md2PdfWorld {
+    fn library(&self) -> &LazyHash<Library> {
+        &self.library
+    }
+
+    fn book(&self) -> &LazyHash<FontBook> {
+        &self.book
+    }
+
+    fn main(&self) -> FileId {
+        self.main.id()
+    }
+
+    fn source(&self, id: FileId) -> FileResult<Source> {
+        if id == self.main.id() {
+            Ok(self.main.clone())
+        } else if let Some(source) = self.files.get(&id) {
+            Ok(source.clone())
+        } else {
+            let text = String::from_utf8(self.read(id)?).map_err(|_| FileError::InvalidUtf8)?;
+            Ok(Source::new(id, text))
+        }
+    }
+
+    fn file(&self, id: FileId) -> FileResult<Bytes> {
+        match self.binaries.get(&id) {
+            Some(data) => Ok(data.clone()),
+            None => self.read(id).map(Bytes::new),
+        }
+    }
+
+    fn font(&self, index: usize) -> Option<Font> {
+        self.fonts.get(index).cloned()
+    }
+
+    fn today(&self, offset: Option<i64>) -> Option<Datetime> {
+        let today = match offset {
+            Some(hours) => date::today_with_offset(self.reproducible, hours)?,
+            None => date::today(self.reproducible)?,
+        };
+        Datetime::from_ymd(today.year(), today.month() as u8, today.day() as u8)
+    }
+}
@@ -0,0 +1,160 @@
+/// A layout instruction written as HTML in Markdown, with a Typst equivalent
+///
+/// Authors mark page breaks and the like with HTML comments such as
+/// `<!-- pagebreak -->`, with the CSS page break properties, or with the
+/// LaTeX `\newpage`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Directive {
+    /// `<!-- pagebreak -->`, `<!-- newpage -->`, or an element styled with
+    /// `page-break-after: always` or `break-before: page`
+    PageBreak,
+    /// `<!-- column-break -->`
+    ColumnBreak,
+    /// `<!-- span -->`: the next table or image spans all columns
+    Span,
+    /// `<!-- vspace 2cm -->`: vertical space
+    Space(String),
+    /// `<!-- landscape start -->` ... `<!-- landscape end -->`
+    Landscape(Boundary),
+    /// `<!-- keep-together start -->` ... `<!-- keep-together end -->`:
+    /// content that is not split across pages
+    KeepTogether(Boundary),
+}
+
+/// Whether a directive opens or closes a section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Boundary {
+    Start,
+    End,
+}
+
+impl Directive {
+    /// The directive in an `Event::Html` or `Event::InlineHtml`, if any
+    pub fn from_html(html: &str) -> Option<Self> {
+        let html = html.trim();
+        if let Some(comment) = html
+            .strip_prefix("<!--")
+            .and_then(|rest| rest.strip_suffix("-->"))
+        {
+            return Self::from_comment(comment);
+        }
+        // An opening tag styled to break the page, such as
+        // `<div style="page-break-after: always"></div>`
+        if html.starts_with('<') && !html.starts_with("</") {
+            let css: String = html
+                .to_ascii_lowercase()
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect();
+            let breaks = [
+                "page-break-before:always",
+                "page-break-after:always",
+                "break-before:page",
+                "break-after:page",
+            ];
+            if breaks.iter().any(|property| css.contains(property)) {
+                return Some(Self::PageBreak);
+            }
+        }
+        None
+    }
+
+    /// What the directive does, for warnings
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::PageBreak => "page break",
+            Self::ColumnBreak => "column break",
+            Self::Span => "span",
+            Self::Space(_) => "vertical space",
+            Self::Landscape(_) => "landscape section",
+            Self::KeepTogether(_) => "keep-together section",
+        }
+    }
+
+    /// The directive in a paragraph of its own, for `\newpage` and friends
+    pub fn from_text(text: &str) -> Option<Self> {
+        match text.trim() {
+            "\\newpage" | "\\pagebreak" | "\\clearpage" => Some(Self::PageBreak),
+            "\\columnbreak" => Some(Self::ColumnBreak),
+            _ => None,
+        }
+    }
+
+    fn from_comment(comment: &str) -> Option<Self> {
+        let comment = comment.trim().to_ascii_lowercase();
+        let words: Vec<&str> = comment.split_whitespace().collect();
+        let boundary = |words: &[&str]| match words {
+            [] | ["start"] => Some(Boundary::Start),
+            ["end"] => Some(Boundary::End),
+            _ => None,
+        };
+        match words.as_slice() {
+            ["pagebreak" | "page-break" | "newpage"] => Some(Self::PageBreak),
+            ["column-break" | "columnbreak" | "colbreak"] => Some(Self::ColumnBreak),
+            ["span"] => Some(Self::Span),
+            ["vspace" | "space", length] if is_length(length) => {
+                Some(Self::Space(length.to_string()))
+            }
+            ["landscape", rest @ ..] => boundary(rest).map(Self::Landscape),
+            ["keep-together", rest @ ..] => boundary(rest).map(Self::KeepTogether),
+            _ => None,
+        }
+    }
+}
+
+/// A Typst length or fraction such as `2cm`, `1.5em` or `1fr`
+fn is_length(value: &str) -> bool {
+    ["pt", "mm", "cm", "in", "em", "fr"]
+        .iter()
+        .filter_map(|unit| value.strip_suffix(unit))
+        .any(|number| number.parse::<f64>().is_ok_and(|n| n >= 0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comments() {
+        assert_eq!(
+            Directive::from_html("<!-- pagebreak -->\n"),
+            Some(Directive::PageBreak)
+        );
+        assert_eq!(
+            Directive::from_html("<!--Column-Break-->"),
+            Some(Directive::ColumnBreak)
+        );
+        assert_eq!(
+            Directive::from_html("<!-- vspace 2.5cm -->"),
+            Some(Directive::Space("2.5cm".to_string()))
+        );
+        assert_eq!(
+            Directive::from_html("<!-- landscape end -->"),
+            Some(Directive::Landscape(Boundary::End))
+        );
+        assert_eq!(
+            Directive::from_html("<!-- keep-together -->"),
+            Some(Directive::KeepTogether(Boundary::Start))
+        );
+        assert_eq!(Directive::from_html("<!-- vspace lots -->"), None);
+        assert_eq!(Directive::from_html("<!-- TODO: pagebreak -->"), None);
+    }
+
+    #[test]
+    fn test_styled_elements_and_latex() {
+        assert_eq!(
+            Directive::from_html("<div style=\"page-break-after: always\"></div>\n"),
+            Some(Directive::PageBreak)
+        );
+        assert_eq!(
+            Directive::from_html("<p style='break-before:page'>"),
+            Some(Directive::PageBreak)
+        );
+        assert_eq!(Directive::from_html("<div class=\"note\">"), None);
+        assert_eq!(
+            Directive::from_text("\\newpage"),
+            Some(Directive::PageBreak)
+        );
+        assert_eq!(Directive::from_text("see \\newpage"), None);
+    }
+}
@@ -0,0 +1,482 @@
+/// A piece of an HTML fragment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    /// An opening or self-closing tag, such as `<img src="a.png">`
+    Start {
+        name: String,
+        attributes: Vec<(String, String)>,
+        source: String,
+    },
+    /// A closing tag, such as `</b>`
+    End { name: String, source: String },
+    /// A comment, `<!--` and `-->` included
+    Comment(String),
+    /// Text between tags, with character references decoded
+    Text(String),
+}
+
+/// Splits HTML into tokens
+///
+/// Markdown hands HTML blocks over line by line, so a tag or comment left
+/// open at the end of one fragment is carried over to the next.
+#[derive(Debug, Default)]
+pub struct Tokenizer {
+    pending: String,
+}
+
+impl Tokenizer {
+    pub fn tokens(&mut self, html: &str) -> Vec<Token> {
+        let input = std::mem::take(&mut self.pending) + html;
+        let mut tokens = Vec::new();
+        let mut rest = input.as_str();
+
+        while !rest.is_empty() {
+            let start = rest.find('<').unwrap_or(rest.len());
+            if start > 0 {
+                tokens.push(Token::Text(decode(&rest[..start])));
+                rest = &rest[start..];
+                continue;
+            }
+
+            let end = if let Some(comment) = rest.strip_prefix("<!--") {
+                comment.find("-->").map(|end| end + 7)
+            } else if rest[1..]
+                .starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!')
+            {
+                tag_end(rest)
+            } else {
+                tokens.push(Token::Text("<".to_string()));
+                rest = &rest[1..];
+                continue;
+            };
+            let Some(end) = end else {
+                self.pending = rest.to_string();
+                break;
+            };
+
+            let source = &rest[..end];
+            if source.starts_with("<!--") {
+                tokens.push(Token::Comment(source.to_string()));
+            } else if let Some(tag) = source.strip_prefix("</") {
+                tokens.push(Token::End {
+                    name: tag_name(tag),
+                    source: source.to_string(),
+                });
+            } else if !source.starts_with("<!") {
+                let tag = source[1..end - 1].trim_end_matches('/');
+                let name = tag_name(tag);
+                tokens.push(Token::Start {
+                    attributes: attributes(&tag[name.len()..]),
+                    name,
+                    source: source.to_string(),
+                });
+            }
+            rest = &rest[end..];
+        }
+
+        tokens
+    }
+}
+
+/// The length of the tag at the start of `html`, up to the `>` outside quotes
+fn tag_end(html: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in html.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '>') => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Attributes as lowercase names and decoded values; a name on its own has
+/// an empty value
+fn attributes(mut html: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    loop {
+        html = html.trim_start();
+        let name_end = html
+            .find(|c: char| c.is_whitespace() || c == '=')
+            .unwrap_or(html.len());
+        if name_end == 0 {
+            break;
+        }
+        let name = html[..name_end].to_ascii_lowercase();
+        html = html[name_end..].trim_start();
+
+        let value = match html.strip_prefix('=') {
+            Some(rest) => {
+                let rest = rest.trim_start();
+                let (value, after) = match rest.chars().next() {
+                    Some(q @ ('"' | '\'')) => {
+                        let end = rest[1..].find(q).map_or(rest.len(), |end| end + 1);
+                        (&rest[1..end], rest.get(end + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                        (&rest[..end], &rest[end..])
+                    }
+                };
+                html = after;
+                decode(value)
+            }
+            None => String::new(),
+        };
+        attributes.push((name, value));
+    }
+    attributes
+}
+
+/// Decode the common named character references and numeric ones
+pub fn decode(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').filter(|end| *end <= 10).and_then(|end| {
+            let c = match &rest[1..end] {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => '\u{a0}',
+                "copy" => '©',
+                "reg" => '®',
+                "trade" => '™',
+                "mdash" => '—',
+                "ndash" => '–',
+                "hellip" => '…',
+                reference => {
+                    let number = reference.strip_prefix('#')?;
+                    let code = match number.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => number.parse().ok()?,
+                    };
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                output.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// What an HTML element becomes in Typst
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Element {
+    /// Content wrapped in markup, such as `#sub[` and `]`
+    Wrap {
+        open: String,
+        close: String,
+    },
+    /// Text kept verbatim, for `<code>` and, as a block, `<pre>`
+    Raw {
+        block: bool,
+    },
+    Heading {
+        level: usize,
+        align: Option<&'static str>,
+    },
+    Link(String),
+    Image {
+        src: String,
+        alt: String,
+        width: Option<String>,
+        height: Option<String>,
+    },
+    /// Markup without content, such as `#linebreak()`
+    Void(&'static str),
+    /// An element that needs no markup of its own, such as `<span>`
+    Transparent,
+    Table,
+    Row,
+    Cell {
+        header: bool,
+        colspan: usize,
+    },
+}
+
+/// The Typst equivalent of an element, or `None` if it is not supported
+pub fn element(name: &str, attributes: &[(String, String)]) -> Option<Element> {
+    let attribute = |key: &str| {
+        attributes
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    };
+    let align = match attribute("align").map(str::to_ascii_lowercase).as_deref() {
+        Some("center") => Some("center"),
+        Some("right") => Some("right"),
+        Some("left") => Some("left"),
+        _ => None,
+    };
+    let wrap = |open: &str, close: &str| {
+        Some(Element::Wrap {
+            open: open.to_string(),
+            close: close.to_string(),
+        })
+    };
+
+    match name {
+        "sub" => wrap("#sub[", "]"),
+        "sup" => wrap("#super[", "]"),
+        "b" | "strong" => wrap("#strong[", "]"),
+        "i" | "em" | "cite" | "var" => wrap("#emph[", "]"),
+        "u" | "ins" => wrap("#underline[", "]"),
+        "s" | "del" | "strike" => wrap("#strike[", "]"),
+        "mark" => wrap("#highlight[", "]"),
+        "small" => wrap("#text(size: 0.8em)[", "]"),
+        "kbd" => wrap(
+            "#box(stroke: 0.5pt + luma(160), inset: (x: 3pt), outset: (y: 3pt), radius: 2pt)[",
+            "]",
+        ),
+        "code" | "tt" | "samp" => Some(Element::Raw { block: false }),
+        "pre" => Some(Element::Raw { block: true }),
+        "center" => wrap("\n#align(center)[", "]\n"),
+        "p" | "div" => match align {
+            Some(align) => wrap(&format!("\n#align({align})[\n"), "]\n"),
+            None if name == "p" => wrap("\n\n", "\n\n"),
+            None => wrap("\n#block[\n", "]\n"),
+        },
+        "blockquote" => wrap("\n#quote(block: true)[\n", "]\n"),
+        "details" => wrap("\n#block[\n", "]\n"),
+        "summary" => wrap("#strong[", "]\n\n"),
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => Some(Element::Heading {
+            level: name[1..].parse().unwrap_or(1),
+            align,
+        }),
+        "a" => Some(match attribute("href") {
+            Some(href) => Element::Link(href.to_string()),
+            None => Element::Transparent,
+        }),
+        "img" => Some(Element::Image {
+            src: attribute("src")?.to_string(),
+            alt: attribute("alt").unwrap_or_default().to_string(),
+            width: attribute("width").and_then(length),
+            height: attribute("height").and_then(length),
+        }),
+        "br" => Some(Element::Void("#linebreak()")),
+        "hr" => Some(Element::Void("\n#line(length: 100%)\n")),
+        "span" | "abbr" | "picture" | "source" | "thead" | "tbody" | "tfoot" => {
+            Some(Element::Transparent)
+        }
+        "table" => Some(Element::Table),
+        "tr" => Some(Element::Row),
+        "td" | "th" => Some(Element::Cell {
+            header: name == "th",
+            colspan: attribute("colspan")
+                .and_then(|n| n.trim().parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(1),
+        }),
+        _ => None,
+    }
+}
+
+/// A `width` or `height` attribute as a Typst length: CSS pixels are 0.75pt
+fn length(value: &str) -> Option<String> {
+    let value = value.trim();
+    if let Some(percent) = value.strip_suffix('%') {
+        return percent.parse::<f64>().ok().map(|_| value.to_string());
+    }
+    let pixels: f64 = value.strip_suffix("px").unwrap_or(value).parse().ok()?;
+    Some(format!("{}pt", pixels * 0.75))
+}
+
+/// An HTML table, collected until it is closed
+#[derive(Debug, Default)]
+pub struct Table {
+    rows: Vec<Vec<Cell>>,
+    cell: Option<Cell>,
+}
+
+#[derive(Debug)]
+struct Cell {
+    content: String,
+    header: bool,
+    colspan: usize,
+}
+
+impl Table {
+    pub fn start_row(&mut self) {
+        self.end_cell();
+        self.rows.push(Vec::new());
+    }
+
+    pub fn start_cell(&mut self, header: bool, colspan: usize) {
+        self.end_cell();
+        self.cell = Some(Cell {
+            content: String::new(),
+            header,
+            colspan,
+        });
+    }
+
+    pub fn end_cell(&mut self) {
+        if let Some(cell) = self.cell.take() {
+            if self.rows.is_empty() {
+                self.rows.push(Vec::new());
+            }
+            if let Some(row) = self.rows.last_mut() {
+                row.push(cell);
+            }
+        }
+    }
+
+    /// The content of the open cell; there is none between cells
+    pub fn cell_mut(&mut self) -> Option<&mut String> {
+        self.cell.as_mut().map(|cell| &mut cell.content)
+    }
+
+    pub fn to_typst(mut self) -> String {
+        self.end_cell();
+        let columns = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.colspan).sum())
+            .max()
+            .unwrap_or(1);
+
+        let mut output = format!("\n#table(\n  columns: {columns},\n");
+        for cell in self.rows.iter().flatten() {
+            let content = cell.content.trim();
+            let content = if cell.header && !content.is_empty() {
+                format!("*{content}*")
+            } else {
+                content.to_string()
+            };
+            if cell.colspan > 1 {
+                output.push_str(&format!(
+                    "  table.cell(colspan: {})[{content}],\n",
+                    cell.colspan
+                ));
+            } else {
+                output.push_str(&format!("  [{content}],\n"));
+            }
+        }
+        output.push_str(")\n\n");
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens() {
+        let mut tokenizer = Tokenizer::default();
+        let tokens = tokenizer.tokens("<img src=\"a.png\" width=200 alt='A &amp; B'/>x &lt; y</p>");
+        assert_eq!(
+            tokens,
+            [
+                Token::Start {
+                    name: "img".to_string(),
+                    attributes: vec![
+                        ("src".to_string(), "a.png".to_string()),
+                        ("width".to_string(), "200".to_string()),
+                        ("alt".to_string(), "A & B".to_string()),
+                    ],
+                    source: "<img src=\"a.png\" width=200 alt='A &amp; B'/>".to_string(),
+                },
+                Token::Text("x < y".to_string()),
+                Token::End {
+                    name: "p".to_string(),
+                    source: "</p>".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tags_and_comments_across_fragments() {
+        let mut tokenizer = Tokenizer::default();
+        assert_eq!(
+            tokenizer.tokens("a <!-- note\n"),
+            [Token::Text("a ".to_string())]
+        );
+        assert_eq!(
+            tokenizer.tokens("more --> <b\n"),
+            [
+                Token::Comment("<!-- note\nmore -->".to_string()),
+                Token::Text(" ".to_string())
+            ]
+        );
+        let tokens = tokenizer.tokens("class=\"x\">1 < 2");
+        assert!(matches!(&tokens[0], Token::Start { name, .. } if name == "b"));
+        assert_eq!(
+            tokens[1..],
+            [
+                Token::Text("1 ".to_string()),
+                Token::Text("<".to_string()),
+                Token::Text(" 2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_elements() {
+        let align = [("align".to_string(), "center".to_string())];
+        assert_eq!(
+            element("p", &align),
+            Some(Element::Wrap {
+                open: "\n#align(center)[\n".to_string(),
+                close: "]\n".to_string()
+            })
+        );
+        let width = [
+            ("src".to_string(), "logo.png".to_string()),
+            ("width".to_string(), "200".to_string()),
+        ];
+        assert_eq!(
+            element("img", &width),
+            Some(Element::Image {
+                src: "logo.png".to_string(),
+                alt: String::new(),
+                width: Some("150pt".to_string()),
+                height: None
+            })
+        );
+        assert_eq!(element("img", &[]), None);
+        assert_eq!(element("marquee", &[]), None);
+    }
+
+    #[test]
+    fn test_table() {
+        let mut table = Table::default();
+        table.start_row();
+        table.start_cell(true, 2);
+        table.cell_mut().unwrap().push_str("Name");
+        table.start_row();
+        table.start_cell(false, 1);
+        table.cell_mut().unwrap().push_str(" a\n");
+        table.start_cell(false, 1);
+        assert_eq!(
+            table.to_typst(),
+            "\n#table(\n  columns: 2,\n  table.cell(colspan: 2)[*Name*],\n  [a],\n  [],\n)\n\n"
+        );
+    }
+}
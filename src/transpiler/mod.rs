@@ -1 +1,3 @@
+pub mod directives;
+pub mod html;
 pub mod typst;
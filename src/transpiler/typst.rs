@@ -1,30 +1,282 @@
-use crate::config::themes::get_theme_preamble;
-use crate::config::Config;
-use crate::parser::frontmatter::Frontmatter;
-use pulldown_cmark::{Alignment, CodeBlockKind, Event, HeadingLevel, Tag, TagEnd};
+use crate::config::themes::{
+    color, get_columns, get_cover, get_marginals, get_theme_preamble, is_color,
+};
+use crate::config::{Config, Marginal};
+use crate::parser::frontmatter::{split_lang, Frontmatter};
+use crate::parser::variables::{evaluate_optional, Variables};
+use crate::transpiler::directives::{Boundary, Directive};
+use crate::transpiler::html::{self, Element, Token, Tokenizer};
+use crate::utils::images::{is_local_image, is_remote_url};
+use crate::{Converted, Md2PdfError, Result};
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, Tag, TagEnd};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
 
-pub fn to_typst(events: Vec<Event<'_>>, frontmatter: &Frontmatter, config: &Config) -> String {
+pub fn to_typst(
+    events: Vec<Event<'_>>,
+    frontmatter: &Frontmatter,
+    config: &Config,
+) -> Result<Converted<String>> {
     let mut converter = TypstConverter::new(config);
-    converter.convert(events, frontmatter)
+    let output = converter.convert(events, frontmatter)?;
+    Ok(Converted {
+        output,
+        warnings: converter.warnings,
+    })
+}
+
+/// Where a chapter sits in a book, for its headings, images and links
+#[derive(Debug, Clone)]
+pub struct ChapterContext<'a> {
+    /// Levels added to every heading, from the chapter's depth in the book
+    pub heading_offset: usize,
+    /// Label of the chapter, also the prefix of its heading labels
+    pub label: String,
+    /// Directory of the chapter file, relative to the book root
+    pub dir: PathBuf,
+    /// Label of every chapter, by file path relative to the book root
+    pub chapters: &'a HashMap<PathBuf, String>,
+}
+
+impl ChapterContext<'_> {
+    /// The label a link points to, if it targets a chapter of the book or a
+    /// heading in one
+    fn resolve_link(&self, url: &str) -> Option<String> {
+        if is_remote_url(url) || url.contains(':') {
+            return None;
+        }
+        let (path, fragment) = match url.split_once('#') {
+            Some((path, fragment)) => (path, Some(fragment)),
+            None => (url, None),
+        };
+
+        let chapter = if path.is_empty() {
+            &self.label
+        } else {
+            self.chapters.get(&normalize(&self.dir.join(path)))?
+        };
+        Some(match fragment {
+            Some(fragment) if !fragment.is_empty() => format!("{chapter}:{fragment}"),
+            _ => chapter.clone(),
+        })
+    }
+}
+
+/// Typst for one chapter of a book: the body only, with headings labelled
+/// and shifted by the chapter's depth, and links between chapters resolved
+pub fn chapter_to_typst(
+    events: Vec<Event<'_>>,
+    chapter: &ChapterContext<'_>,
+    config: &Config,
+) -> Converted<String> {
+    let mut converter = TypstConverter::new(config);
+    converter.chapter = Some(chapter);
+    converter
+        .output
+        .push_str(&format!("#metadata(none) <{}>\n", chapter.label));
+    for event in events {
+        converter.process_event(event);
+    }
+    converter.finish();
+    Converted {
+        output: converter.output,
+        warnings: converter.warnings,
+    }
+}
+
+/// The start of a book: theme, document metadata, heading numbering, a
+/// title page and the table of contents
+pub fn book_preamble(
+    frontmatter: &Frontmatter,
+    numbering: Option<&str>,
+    outline: bool,
+    config: &Config,
+) -> Result<String> {
+    let mut output = get_theme_preamble(config)?;
+    output.push('\n');
+    output.push_str(&document_rule(frontmatter));
+    if let Some(lang) = &frontmatter.lang {
+        output.push_str(&lang_rule(lang));
+    }
+    let (header, footer) = get_marginals(config)?;
+    output.push_str(&marginals_rule(
+        header.as_ref(),
+        footer.as_ref(),
+        frontmatter,
+    )?);
+    if numbering.is_some() {
+        output.push_str(&numbering_rule(numbering));
+    }
+
+    if let Some(layout) = &frontmatter.cover {
+        output.push('\n');
+        output.push_str(&cover_page(layout, frontmatter, config)?);
+    } else if let Some(title) = &frontmatter.title {
+        let mut lines = vec![format!(
+            "text(size: 28pt, weight: \"bold\")[{}]",
+            escape_typst_text(title)
+        )];
+        if let Some(author) = &frontmatter.author {
+            lines.push(format!("text(size: 14pt)[{}]", escape_typst_text(author)));
+        }
+        if let Some(date) = frontmatter.display_date() {
+            lines.push(format!(
+                "text(size: 11pt, style: \"italic\")[{}]",
+                escape_typst_text(&date)
+            ));
+        }
+        output.push_str(&format!(
+            "\n#page(header: none, footer: none, columns: 1, align(center + horizon, stack(spacing: 1.5em, {})))\n",
+            lines.join(", ")
+        ));
+    }
+
+    if outline {
+        output.push_str("\n#outline()\n#pagebreak()\n");
+    }
+
+    Ok(output)
+}
+
+/// The cover page in `layout`, after a `cover` dictionary of the fields it
+/// shows; page numbers start again from the page after it
+pub fn cover_page(layout: &str, frontmatter: &Frontmatter, config: &Config) -> Result<String> {
+    let text = |value: Option<String>| match value {
+        Some(value) => format!("\"{}\"", escape_typst_string(&value)),
+        None => "none".to_string(),
+    };
+    let background = frontmatter.extra_text("cover_background");
+    if let Some(value) = background.as_deref() {
+        if !config.raw_typst && !is_color(value) {
+            return Err(Md2PdfError::InvalidInput(format!(
+                "invalid cover_background `{value}`; expected a color such as #336699 or blue"
+            )));
+        }
+    }
+    let fields = [
+        ("title", text(frontmatter.title.clone())),
+        ("subtitle", text(frontmatter.extra_text("subtitle"))),
+        ("author", text(frontmatter.author.clone())),
+        ("date", text(frontmatter.display_date())),
+        ("version", text(frontmatter.extra_text("version"))),
+        (
+            "organization",
+            text(
+                frontmatter
+                    .extra_text("organization")
+                    .or_else(|| frontmatter.extra_text("organisation")),
+            ),
+        ),
+        (
+            "classification",
+            text(frontmatter.extra_text("classification")),
+        ),
+        ("logo", text(frontmatter.extra_text("logo"))),
+        (
+            "background",
+            background.map_or("none".to_string(), |value| color(&value)),
+        ),
+    ];
+
+    let mut output = String::from("#let cover = (\n");
+    for (name, value) in fields {
+        output.push_str(&format!("  {name}: {value},\n"));
+    }
+    output.push_str(")\n");
+    output.push_str(get_cover(layout, config)?.trim_end());
+    output.push_str("\n#counter(page).update(1)\n\n");
+    Ok(output)
+}
+
+/// A set rule for heading numbering; `None` turns numbering off
+pub fn numbering_rule(numbering: Option<&str>) -> String {
+    match numbering {
+        Some(numbering) => format!(
+            "#set heading(numbering: \"{}\")\n",
+            escape_typst_string(numbering)
+        ),
+        None => "#set heading(numbering: none)\n".to_string(),
+    }
+}
+
+/// A part title on a page of its own, listed in the table of contents
+pub fn part_heading(title: &str) -> String {
+    format!(
+        "\n#align(center + horizon, heading(level: 1, numbering: none)[{}])\n#pagebreak()\n",
+        escape_typst_text(title)
+    )
+}
+
+/// A heading for a book section that has no file of its own
+pub fn section_heading(title: &str, depth: usize) -> String {
+    format!(
+        "\n{} {}\n\n",
+        "=".repeat(depth + 1),
+        escape_typst_text(title)
+    )
 }
 
 struct TypstConverter<'a> {
     config: &'a Config,
+    chapter: Option<&'a ChapterContext<'a>>,
     output: String,
     list_stack: Vec<ListContext>,
     in_table: bool,
     table_alignments: Vec<Alignment>,
     table_row: Vec<String>,
     current_cell: String,
+    /// The next table or image spans all columns, after `<!-- span -->`
+    span_next: bool,
+    /// `span_next` was set before the current paragraph began
+    span_paragraph: bool,
+    /// The open table spans all columns
+    span_table: bool,
+    /// Landscape and keep-together sections opened by directives, innermost last
+    sections: Vec<Section>,
     in_heading: bool,
+    heading_text: String,
+    /// Times each heading slug was used, to make labels unique
+    heading_slugs: HashMap<String, usize>,
     in_emphasis: bool,
     in_strong: bool,
     in_strikethrough: bool,
     in_link: bool,
     link_url: String,
+    /// Label of the chapter or heading a link points to, within a book
+    link_label: Option<String>,
+    in_image: bool,
+    image_url: String,
+    image_alt: String,
     in_code_block: bool,
     code_block_lang: Option<String>,
     code_block_content: String,
+    /// Inline code not yet written, see `process_event`
+    pending_code: Option<String>,
+    html: Tokenizer,
+    /// HTML elements open in the output, innermost last
+    html_elements: Vec<HtmlElement>,
+    /// Depth of `html_elements` where each open paragraph, heading and table
+    /// cell began; elements opened inside one are closed when it ends
+    html_depths: Vec<usize>,
+    /// HTML tables being collected, innermost last
+    html_tables: Vec<html::Table>,
+    /// Problems that don't stop the conversion, each once
+    warnings: Vec<String>,
+}
+
+/// An open HTML element and the markup that closes it
+struct HtmlElement {
+    name: String,
+    close: String,
+    /// Text inside is kept verbatim in a Typst string
+    raw: bool,
+}
+
+/// A section of the document wrapped in a Typst element by directives
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Landscape,
+    KeepTogether,
 }
 
 #[derive(Clone)]
@@ -37,35 +289,79 @@ impl<'a> TypstConverter<'a> {
     fn new(config: &'a Config) -> Self {
         Self {
             config,
+            chapter: None,
             output: String::new(),
             list_stack: Vec::new(),
             in_table: false,
             table_alignments: Vec::new(),
             table_row: Vec::new(),
             current_cell: String::new(),
+            span_next: false,
+            span_paragraph: false,
+            span_table: false,
+            sections: Vec::new(),
             in_heading: false,
+            heading_text: String::new(),
+            heading_slugs: HashMap::new(),
             in_emphasis: false,
             in_strong: false,
             in_strikethrough: false,
             in_link: false,
             link_url: String::new(),
+            link_label: None,
+            in_image: false,
+            image_url: String::new(),
+            image_alt: String::new(),
             in_code_block: false,
             code_block_lang: None,
             code_block_content: String::new(),
+            pending_code: None,
+            html: Tokenizer::default(),
+            html_elements: Vec::new(),
+            html_depths: Vec::new(),
+            html_tables: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn warn(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        if !self.warnings.contains(&message) {
+            self.warnings.push(message);
         }
     }
 
-    fn convert(&mut self, events: Vec<Event<'_>>, frontmatter: &Frontmatter) -> String {
+    fn convert(&mut self, events: Vec<Event<'_>>, frontmatter: &Frontmatter) -> Result<String> {
         // Add theme preamble
-        self.output.push_str(&get_theme_preamble(
-            &self.config.theme,
-            self.config.paper_typst(),
-        ));
+        self.output.push_str(&get_theme_preamble(self.config)?);
         self.output.push('\n');
 
-        // Add frontmatter header if present
+        // Document metadata, required for PDF/UA and shown by PDF viewers
+        self.output.push_str(&document_rule(frontmatter));
+
+        // Language for hyphenation, quotes and localized terms
+        if let Some(lang) = &frontmatter.lang {
+            self.output.push_str(&lang_rule(lang));
+        }
+
+        let (header, footer) = get_marginals(self.config)?;
+        self.output.push_str(&marginals_rule(
+            header.as_ref(),
+            footer.as_ref(),
+            frontmatter,
+        )?);
+
+        // A cover page, or the title block at the top of the first page
         let header = frontmatter.to_typst_header();
-        if !header.is_empty() {
+        if let Some(layout) = &frontmatter.cover {
+            self.output
+                .push_str(&cover_page(layout, frontmatter, self.config)?);
+        } else if !header.is_empty() && get_columns(self.config)? > 1 {
+            // Above the columns, across the full width of the page
+            self.output.push_str(&format!(
+                "#place(top, float: true, scope: \"parent\", clearance: 2em)[\n{header}]\n\n"
+            ));
+        } else if !header.is_empty() {
             self.output.push_str(&header);
             self.output.push('\n');
         }
@@ -73,16 +369,49 @@ impl<'a> TypstConverter<'a> {
         for event in events {
             self.process_event(event);
         }
+        self.finish();
 
-        self.output.clone()
+        Ok(self.output.clone())
     }
 
     fn process_event(&mut self, event: Event<'_>) {
+        // Inline code waits for the next event, in case `{=typst}` follows it
+        if let Some(code) = self.pending_code.take() {
+            if let Event::Text(text) = &event {
+                if let Some(rest) = text.strip_prefix(RAW_TYPST) {
+                    self.raw_typst_inline(&code);
+                    if !rest.is_empty() {
+                        self.text(rest);
+                    }
+                    return;
+                }
+            }
+            self.inline_code(&code);
+        }
+
+        // `<!-- span -->` applies to the next table or image, so any other
+        // block, or a paragraph without an image, uses it up
+        match &event {
+            Event::Start(Tag::Paragraph) => self.span_paragraph = self.span_next,
+            Event::End(TagEnd::Paragraph) if self.span_paragraph => self.span_next = false,
+            Event::Start(
+                Tag::Heading { .. }
+                | Tag::BlockQuote(_)
+                | Tag::CodeBlock(_)
+                | Tag::List(_)
+                | Tag::HtmlBlock
+                | Tag::FootnoteDefinition(_)
+                | Tag::DefinitionList,
+            )
+            | Event::Rule => self.span_next = false,
+            _ => {}
+        }
+
         match event {
             Event::Start(tag) => self.start_tag(tag),
             Event::End(tag) => self.end_tag(tag),
             Event::Text(text) => self.text(&text),
-            Event::Code(code) => self.inline_code(&code),
+            Event::Code(code) => self.pending_code = Some(code.into_string()),
             Event::Html(html) => self.html(&html),
             Event::InlineHtml(html) => self.html(&html),
             Event::SoftBreak => self.soft_break(),
@@ -98,27 +427,23 @@ impl<'a> TypstConverter<'a> {
     fn start_tag(&mut self, tag: Tag<'_>) {
         match tag {
             Tag::Paragraph => {
-                if !self.in_table {
-                    // Don't add extra newlines at the start
-                    if !self.output.trim().is_empty() && !self.output.ends_with('\n') {
-                        self.output.push('\n');
-                    }
+                self.html_depths.push(self.html_elements.len());
+                // Don't add extra newlines at the start
+                if !self.in_table && !self.output.trim().is_empty() && !self.output.ends_with('\n')
+                {
+                    self.output.push('\n');
                 }
             }
             Tag::Heading { level, .. } => {
+                self.html_depths.push(self.html_elements.len());
                 self.in_heading = true;
-                let prefix = match level {
-                    HeadingLevel::H1 => "= ",
-                    HeadingLevel::H2 => "== ",
-                    HeadingLevel::H3 => "=== ",
-                    HeadingLevel::H4 => "==== ",
-                    HeadingLevel::H5 => "===== ",
-                    HeadingLevel::H6 => "====== ",
-                };
+                self.heading_text.clear();
+                let offset = self.chapter.map_or(0, |c| c.heading_offset);
                 if !self.output.is_empty() {
                     self.output.push('\n');
                 }
-                self.output.push_str(prefix);
+                self.output.push_str(&"=".repeat(level as usize + offset));
+                self.output.push(' ');
             }
             Tag::BlockQuote(_) => {
                 self.output.push_str("\n#quote(block: true)[\n");
@@ -150,27 +475,34 @@ impl<'a> TypstConverter<'a> {
             }
             Tag::Emphasis => {
                 self.in_emphasis = true;
-                self.output.push('_');
+                self.write("_");
             }
             Tag::Strong => {
                 self.in_strong = true;
-                self.output.push('*');
+                self.write("*");
             }
             Tag::Strikethrough => {
                 self.in_strikethrough = true;
-                self.output.push_str("#strike[");
+                self.write("#strike[");
             }
             Tag::Link { dest_url, .. } => {
                 self.in_link = true;
                 self.link_url = dest_url.to_string();
+                self.link_label = self.chapter.and_then(|c| c.resolve_link(&dest_url));
             }
             Tag::Image { dest_url, .. } => {
-                self.output
-                    .push_str(&format!("#image(\"{}\")", escape_typst_string(&dest_url)));
+                // The alt text arrives as text events before the end tag
+                self.in_image = true;
+                self.image_url = self.image_path(&dest_url);
+                self.image_alt.clear();
             }
             Tag::Table(alignments) => {
                 self.in_table = true;
                 self.table_alignments = alignments;
+                self.span_table = std::mem::take(&mut self.span_next);
+                if self.span_table {
+                    self.output.push_str(&format!("\n{SPAN}["));
+                }
                 self.output.push_str("\n#table(\n  columns: (");
                 let cols: Vec<&str> = self.table_alignments.iter().map(|_| "auto").collect();
                 self.output.push_str(&cols.join(", "));
@@ -195,6 +527,7 @@ impl<'a> TypstConverter<'a> {
                 self.table_row.clear();
             }
             Tag::TableCell => {
+                self.html_depths.push(self.html_elements.len());
                 self.current_cell.clear();
             }
             _ => {}
@@ -204,12 +537,25 @@ impl<'a> TypstConverter<'a> {
     fn end_tag(&mut self, tag: TagEnd) {
         match tag {
             TagEnd::Paragraph => {
+                self.close_container_html();
                 if !self.in_table {
                     self.output.push_str("\n\n");
                 }
             }
             TagEnd::Heading(_) => {
+                self.close_container_html();
                 self.in_heading = false;
+                if let Some(chapter) = self.chapter {
+                    let slug = slugify(&self.heading_text);
+                    let count = self.heading_slugs.entry(slug.clone()).or_insert(0);
+                    let slug = match *count {
+                        0 => slug,
+                        n => format!("{slug}-{n}"),
+                    };
+                    *count += 1;
+                    self.output
+                        .push_str(&format!(" <{}:{}>", chapter.label, slug));
+                }
                 self.output.push_str("\n\n");
             }
             TagEnd::BlockQuote(_) => {
@@ -217,11 +563,22 @@ impl<'a> TypstConverter<'a> {
             }
             TagEnd::CodeBlock => {
                 self.in_code_block = false;
-                let lang = self.code_block_lang.take();
+                let mut lang = self.code_block_lang.take();
                 let content = std::mem::take(&mut self.code_block_content);
 
-                // Escape backticks in the content by using more backticks
-                let backticks = "```";
+                if lang.as_deref() == Some(RAW_TYPST) {
+                    if self.config.raw_typst {
+                        self.output
+                            .push_str(&format!("\n{}\n\n", content.trim_end()));
+                        return;
+                    }
+                    self.warn("raw Typst is disabled; a `{=typst}` block is shown as code");
+                    lang = Some("typ".to_string());
+                }
+
+                // A longer fence than any backticks in the content, which
+                // would otherwise end the block and let the rest run as Typst
+                let backticks = &fence(&content);
 
                 if let Some(lang) = lang {
                     self.output.push_str(&format!(
@@ -246,31 +603,39 @@ impl<'a> TypstConverter<'a> {
                     self.output.push('\n');
                 }
             }
-            TagEnd::Item => {
-                if !self.output.ends_with('\n') {
-                    self.output.push('\n');
-                }
-            }
+            TagEnd::Item if !self.output.ends_with('\n') => self.output.push('\n'),
             TagEnd::Emphasis => {
                 self.in_emphasis = false;
-                self.output.push('_');
+                self.write("_");
             }
             TagEnd::Strong => {
                 self.in_strong = false;
-                self.output.push('*');
+                self.write("*");
             }
             TagEnd::Strikethrough => {
                 self.in_strikethrough = false;
-                self.output.push(']');
+                self.write("]");
             }
             TagEnd::Link => {
                 self.in_link = false;
+                self.link_label = None;
                 // URL may have already been consumed by text() handler
                 self.link_url.clear();
             }
+            TagEnd::Image => {
+                self.in_image = false;
+                let url = std::mem::take(&mut self.image_url);
+                let alt = std::mem::take(&mut self.image_alt);
+                let image = self.image(&url, &alt, &[]);
+                self.write(&image);
+            }
             TagEnd::Table => {
                 self.in_table = false;
-                self.output.push_str(")\n\n");
+                self.output.push_str(")\n");
+                if std::mem::take(&mut self.span_table) {
+                    self.output.push(']');
+                }
+                self.output.push('\n');
             }
             TagEnd::TableHead => {
                 // Output header row with bold
@@ -279,13 +644,12 @@ impl<'a> TypstConverter<'a> {
                 }
             }
             TagEnd::TableRow => {
-                if !self.table_row.is_empty() {
-                    for cell in &self.table_row {
-                        self.output.push_str(&format!("  [{}],\n", cell));
-                    }
+                for cell in &self.table_row {
+                    self.output.push_str(&format!("  [{}],\n", cell));
                 }
             }
             TagEnd::TableCell => {
+                self.close_container_html();
                 let cell = std::mem::take(&mut self.current_cell);
                 self.table_row.push(cell);
             }
@@ -299,40 +663,351 @@ impl<'a> TypstConverter<'a> {
             return;
         }
 
-        let escaped = escape_typst_text(text);
+        if !self.in_table && !self.in_image && !self.in_heading && !self.in_link {
+            if let Some(directive) = Directive::from_text(text) {
+                self.directive(directive);
+                return;
+            }
+        }
+
+        if self.in_image {
+            self.image_alt.push_str(text);
+            return;
+        }
+
+        if self.in_heading {
+            self.heading_text.push_str(text);
+        }
+
+        let escaped = if self.in_html_raw() {
+            escape_typst_string(text)
+        } else {
+            escape_typst_text(text)
+        };
 
         if self.in_table {
             self.current_cell.push_str(&escaped);
+        } else if let Some(label) = self.link_label.take().filter(|_| self.in_link) {
+            self.write(&format!("#link(<{}>)[{}]", label, escaped));
+            self.link_url.clear();
         } else if self.in_link {
             // For links, we need to handle it differently
-            self.output.push_str(&format!(
+            let link = format!(
                 "#link(\"{}\")[{}]",
                 escape_typst_string(&self.link_url),
                 escaped
-            ));
+            );
+            self.write(&link);
             self.link_url.clear(); // Clear so end_tag doesn't duplicate
         } else {
-            self.output.push_str(&escaped);
+            self.write(&escaped);
+        }
+    }
+
+    /// Add markup to the output, or to the table cell being collected
+    fn write(&mut self, markup: &str) {
+        if let Some(table) = self.html_tables.last_mut() {
+            // Anything between the cells of an HTML table is left out
+            if let Some(cell) = table.cell_mut() {
+                cell.push_str(markup);
+            }
+        } else if self.in_table {
+            self.current_cell.push_str(markup);
+        } else {
+            self.output.push_str(markup);
+        }
+    }
+
+    /// The path of an image in the Typst source
+    fn image_path(&self, url: &str) -> String {
+        match self.chapter {
+            // Chapter images are relative to the chapter, not the book root
+            Some(chapter) if is_local_image(url) && !is_remote_url(url) => {
+                normalize(&chapter.dir.join(url))
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            }
+            _ => url.to_string(),
+        }
+    }
+
+    /// `#image(...)`, spanning the columns after `<!-- span -->`
+    fn image(&mut self, path: &str, alt: &str, size: &[(&str, String)]) -> String {
+        let mut arguments = format!("\"{}\"", escape_typst_string(path));
+        for (name, value) in size {
+            arguments.push_str(&format!(", {name}: {value}"));
+        }
+        if !alt.trim().is_empty() {
+            arguments.push_str(&format!(", alt: \"{}\"", escape_typst_string(alt.trim())));
+        }
+        let image = format!("#image({arguments})");
+        if std::mem::take(&mut self.span_next) {
+            format!("{SPAN}[{image}]")
+        } else {
+            image
         }
     }
 
     fn inline_code(&mut self, code: &str) {
-        if self.in_table {
-            self.current_cell.push_str(&format!("`{}`", code));
+        if self.in_heading {
+            self.heading_text.push_str(code);
+        }
+        if self.in_image {
+            self.image_alt.push_str(code);
+        } else {
+            self.write(&code_span(code));
+        }
+    }
+
+    fn html(&mut self, html: &str) {
+        for token in self.html.tokens(html) {
+            match token {
+                Token::Comment(source) => {
+                    if let Some(code) = raw_typst_comment(&source) {
+                        self.raw_typst_comment(code);
+                    } else if let Some(directive) = Directive::from_html(&source) {
+                        self.directive(directive);
+                    }
+                }
+                Token::Text(text) if self.in_html_raw() => {
+                    // As in HTML, a newline straight after `<pre>` is not shown
+                    let text = match self.output.strip_suffix(RAW_BLOCK) {
+                        Some(_) => text.strip_prefix('\n').unwrap_or(&text),
+                        None => &text,
+                    };
+                    self.write(&escape_typst_string(text));
+                }
+                Token::Text(text) => self.write(&escape_typst_text(&text)),
+                Token::Start {
+                    name,
+                    attributes,
+                    source,
+                } => self.html_start(&name, &attributes, &source),
+                Token::End { name, source } => self.html_end(&name, &source),
+            }
+        }
+    }
+
+    fn raw_typst_comment(&mut self, code: &str) {
+        if self.config.raw_typst {
+            self.write(code.trim());
+        } else {
+            self.warn("raw Typst is disabled; a `<!--typst -->` comment is left out");
+        }
+    }
+
+    fn raw_typst_inline(&mut self, code: &str) {
+        if self.config.raw_typst {
+            self.write(code);
+        } else {
+            self.warn("raw Typst is disabled; inline `{=typst}` code is shown as code");
+            self.inline_code(code);
+        }
+    }
+
+    fn html_start(&mut self, name: &str, attributes: &[(String, String)], source: &str) {
+        if let Some(directive) = Directive::from_html(source) {
+            self.directive(directive);
+            return;
+        }
+        // Tags within `<code>` and `<pre>` are left out
+        if self.in_html_raw() {
+            return;
+        }
+
+        let (open, close, raw) = match html::element(name, attributes) {
+            None => return self.unsupported_html(name, source),
+            Some(Element::Wrap { open, close }) => (open, close, false),
+            Some(Element::Raw { block: false }) => ("#raw(\"".to_string(), "\")".to_string(), true),
+            Some(Element::Raw { block: true }) => {
+                (format!("\n{RAW_BLOCK}"), "\")\n".to_string(), true)
+            }
+            Some(Element::Heading { level, align }) => {
+                let level = level + self.chapter.map_or(0, |c| c.heading_offset);
+                let heading = format!("#heading(level: {level})[");
+                match align {
+                    Some(align) => (
+                        format!("\n#align({align})[{heading}"),
+                        "]]\n".to_string(),
+                        false,
+                    ),
+                    None => (format!("\n{heading}"), "]\n".to_string(), false),
+                }
+            }
+            Some(Element::Link(href)) => {
+                let open = match self.chapter.and_then(|c| c.resolve_link(&href)) {
+                    Some(label) => format!("#link(<{label}>)["),
+                    None => format!("#link(\"{}\")[", escape_typst_string(&href)),
+                };
+                (open, "]".to_string(), false)
+            }
+            Some(Element::Image {
+                src,
+                alt,
+                width,
+                height,
+            }) => {
+                let size: Vec<(&str, String)> = [("width", width), ("height", height)]
+                    .into_iter()
+                    .filter_map(|(name, value)| Some((name, value?)))
+                    .collect();
+                let path = self.image_path(&src);
+                let image = self.image(&path, &alt, &size);
+                return self.write(&image);
+            }
+            Some(Element::Void(markup)) => return self.write(markup),
+            Some(Element::Transparent) => return,
+            Some(Element::Table) => {
+                self.html_tables.push(html::Table::default());
+                return;
+            }
+            Some(Element::Row) => {
+                if let Some(table) = self.html_tables.last_mut() {
+                    table.start_row();
+                }
+                return;
+            }
+            Some(Element::Cell { header, colspan }) => {
+                if let Some(table) = self.html_tables.last_mut() {
+                    table.start_cell(header, colspan);
+                }
+                return;
+            }
+        };
+        self.write(&open);
+        self.html_elements.push(HtmlElement {
+            name: name.to_string(),
+            close,
+            raw,
+        });
+    }
+
+    fn html_end(&mut self, name: &str, source: &str) {
+        match name {
+            "table" => {
+                if let Some(table) = self.html_tables.pop() {
+                    self.write(&table.to_typst());
+                }
+            }
+            "td" | "th" => {
+                if let Some(table) = self.html_tables.last_mut() {
+                    table.end_cell();
+                }
+            }
+            _ if html::element(name, &[]).is_none() && name != "img" => {
+                if self.config.keep_html && !self.in_html_raw() {
+                    self.write(&escape_typst_text(source));
+                }
+            }
+            _ => {
+                // A closing tag without an opening one is left out
+                if let Some(index) = self.html_elements.iter().rposition(|e| e.name == name) {
+                    self.close_html(index);
+                }
+            }
+        }
+    }
+
+    /// Close the HTML elements opened in the paragraph, heading or table cell
+    /// that is ending
+    fn close_container_html(&mut self) {
+        if let Some(depth) = self.html_depths.pop() {
+            self.close_html(depth);
+        }
+    }
+
+    /// Close the HTML elements from `depth` inwards
+    fn close_html(&mut self, depth: usize) {
+        while self.html_elements.len() > depth {
+            if let Some(element) = self.html_elements.pop() {
+                self.write(&element.close);
+            }
+        }
+    }
+
+    fn unsupported_html(&mut self, name: &str, source: &str) {
+        if self.config.keep_html {
+            self.warn(format!("unsupported HTML `<{name}>` shown as text"));
+            self.write(&escape_typst_text(source));
         } else {
-            self.output.push_str(&format!("`{}`", code));
+            self.warn(format!("unsupported HTML `<{name}>` left out"));
+        }
+    }
+
+    fn in_html_raw(&self) -> bool {
+        self.html_elements.iter().any(|e| e.raw)
+    }
+
+    /// Close whatever HTML and directives left open at the end
+    fn finish(&mut self) {
+        if let Some(code) = self.pending_code.take() {
+            self.inline_code(&code);
+        }
+        self.close_html(0);
+        while let Some(table) = self.html_tables.pop() {
+            self.write(&table.to_typst());
+        }
+        self.close_sections(0);
+    }
+
+    fn directive(&mut self, directive: Directive) {
+        // A table cell cannot break the page or column, or hold a section
+        let in_table = self.in_table || !self.html_tables.is_empty();
+        if in_table && !matches!(directive, Directive::Space(_)) {
+            self.warn(format!("{} in a table left out", directive.description()));
+            return;
+        }
+
+        match directive {
+            Directive::PageBreak => self.write("\n#pagebreak()\n"),
+            Directive::ColumnBreak => self.write("\n#colbreak()\n"),
+            Directive::Span => self.span_next = true,
+            Directive::Space(length) => self.write(&format!("\n#v({length})\n")),
+            Directive::Landscape(boundary) => self.section(Section::Landscape, boundary),
+            Directive::KeepTogether(boundary) => self.section(Section::KeepTogether, boundary),
+        }
+    }
+
+    /// Open a section, or close it along with any opened inside it; an end
+    /// without a start is ignored
+    fn section(&mut self, section: Section, boundary: Boundary) {
+        match boundary {
+            Boundary::Start => {
+                let element = match section {
+                    Section::Landscape => {
+                        // Turned from the document's paper, whatever it is
+                        let page = self.config.paper().map_or_else(
+                            |_| "flipped: true".to_string(),
+                            |paper| paper.landscape().page_arguments(),
+                        );
+                        format!("#page({page})")
+                    }
+                    Section::KeepTogether => "#block(breakable: false)".to_string(),
+                };
+                self.write(&format!("\n{element}[\n"));
+                self.sections.push(section);
+            }
+            Boundary::End => {
+                if let Some(index) = self.sections.iter().rposition(|s| *s == section) {
+                    self.close_sections(index);
+                }
+            }
         }
     }
 
-    fn html(&mut self, _html: &str) {
-        // HTML is not supported, skip it
+    /// Close the sections from `depth` inwards
+    fn close_sections(&mut self, depth: usize) {
+        while self.sections.len() > depth {
+            self.sections.pop();
+            self.write("\n]\n");
+        }
     }
 
     fn soft_break(&mut self) {
         if self.in_code_block {
             self.code_block_content.push('\n');
         } else if !self.in_table {
-            self.output.push(' ');
+            self.write(" ");
         }
     }
 
@@ -340,7 +1015,7 @@ impl<'a> TypstConverter<'a> {
         if self.in_code_block {
             self.code_block_content.push('\n');
         } else {
-            self.output.push_str(" \\\n");
+            self.write(" \\\n");
         }
     }
 
@@ -349,20 +1024,264 @@ impl<'a> TypstConverter<'a> {
     }
 
     fn task_list_marker(&mut self, checked: bool) {
-        let marker = if checked { "[x]" } else { "[ ]" };
-        self.output.push_str(marker);
-        self.output.push(' ');
+        let marker = if checked { "[x] " } else { "[ ] " };
+        self.write(marker);
     }
 
     fn inline_math(&mut self, math: &str) {
-        self.output.push_str(&format!("${}$", math));
+        let math = self.math(math);
+        self.write(&format!("${}$", math));
     }
 
     fn display_math(&mut self, math: &str) {
-        self.output.push_str(&format!("\n$ {} $\n", math));
+        let math = self.math(math);
+        self.write(&format!("\n$ {} $\n", math));
+    }
+
+    /// Math as written, or with `#` escaped when raw Typst is off, as it
+    /// would start Typst code
+    fn math(&mut self, math: &str) -> String {
+        if self.config.raw_typst || !math.contains('#') {
+            return math.to_string();
+        }
+        self.warn("raw Typst is disabled; `#` in math is shown as text");
+        let mut escaped = String::new();
+        let mut chars = math.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                // Already escaped, such as `\#`
+                '\\' => {
+                    escaped.push(c);
+                    escaped.extend(chars.next());
+                }
+                '#' => escaped.push_str("\\#"),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
+}
+
+/// Marks code blocks and inline code as Typst to insert verbatim
+const RAW_TYPST: &str = "{=typst}";
+
+/// The Typst in a `<!--typst ... -->` comment
+fn raw_typst_comment(comment: &str) -> Option<&str> {
+    let code = comment.strip_prefix("<!--typst")?.strip_suffix("-->")?;
+    code.starts_with(char::is_whitespace).then_some(code)
+}
+
+/// The start of a `<pre>` block
+const RAW_BLOCK: &str = "#raw(block: true, \"";
+
+/// A float above or below the columns, across the full width of the page
+const SPAN: &str = "#place(auto, float: true, scope: \"parent\", clearance: 1.5em)";
+
+/// `#set document(...)` with the title, author and keywords from frontmatter
+fn document_rule(frontmatter: &Frontmatter) -> String {
+    let mut args = Vec::new();
+
+    if let Some(title) = &frontmatter.title {
+        args.push(format!("title: \"{}\"", escape_typst_string(title)));
+    }
+    if let Some(author) = &frontmatter.author {
+        args.push(format!("author: \"{}\"", escape_typst_string(author)));
+    }
+    if !frontmatter.keywords.is_empty() {
+        let keywords: Vec<String> = frontmatter
+            .keywords
+            .iter()
+            .map(|k| format!("\"{}\"", escape_typst_string(k)))
+            .collect();
+        args.push(format!("keywords: ({},)", keywords.join(", ")));
+    }
+
+    if args.is_empty() {
+        String::new()
+    } else {
+        format!("#set document({})\n", args.join(", "))
+    }
+}
+
+/// `#set page(header:, footer:)` with the templates filled in from the
+/// frontmatter and the page being laid out
+pub fn marginals_rule(
+    header: Option<&Marginal>,
+    footer: Option<&Marginal>,
+    frontmatter: &Frontmatter,
+) -> Result<String> {
+    let vars = frontmatter.variables();
+    let mut args = Vec::new();
+    for (name, marginal) in [("header", header), ("footer", footer)] {
+        if let Some(marginal) = marginal {
+            if let Some(content) = marginal_content(marginal, &vars)? {
+                args.push(format!("{name}: {content}"));
+            }
+        }
+    }
+
+    if args.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(format!("#set page({})\n", args.join(", ")))
+}
+
+fn marginal_content(marginal: &Marginal, vars: &Variables) -> Result<Option<String>> {
+    let odd = marginal_row([&marginal.left, &marginal.center, &marginal.right], vars)?;
+    let even = if [
+        &marginal.even_left,
+        &marginal.even_center,
+        &marginal.even_right,
+    ]
+    .iter()
+    .any(|part| part.is_some())
+    {
+        marginal_row(
+            [
+                &marginal.even_left,
+                &marginal.even_center,
+                &marginal.even_right,
+            ],
+            vars,
+        )?
+    } else {
+        odd.clone()
+    };
+    if odd.is_none() && even.is_none() {
+        return Ok(None);
+    }
+
+    let odd = odd.unwrap_or_else(|| "none".to_string());
+    let even = even.unwrap_or_else(|| "none".to_string());
+    let mut body = if odd == even {
+        odd
+    } else {
+        format!("if calc.even(here().page()) {{ {even} }} else {{ {odd} }}")
+    };
+    // Page 1 by number, so that after a cover it is the first page of text
+    if marginal.first_page == Some(false) {
+        body = format!("if counter(page).get().first() == 1 {{ none }} else {{ {body} }}");
+    }
+    Ok(Some(format!("context {{ {body} }}")))
+}
+
+/// A left, center and right aligned row, or `None` if all are unset
+fn marginal_row(parts: [&Option<String>; 3], vars: &Variables) -> Result<Option<String>> {
+    if parts
+        .iter()
+        .all(|part| part.as_deref().unwrap_or("").is_empty())
+    {
+        return Ok(None);
+    }
+    let cells = parts
+        .iter()
+        .zip(["left", "center", "right"])
+        .map(|(part, align)| {
+            let content = match part.as_deref() {
+                Some(template) if !template.is_empty() => marginal_template(template, vars)?,
+                _ => "[]".to_string(),
+            };
+            Ok(format!("align({align}, {content})"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(format!(
+        "grid(columns: (1fr, auto, 1fr), {})",
+        cells.join(", ")
+    )))
+}
+
+/// A header or footer template as a Typst code block
+///
+/// `{{ page }}`, `{{ total }}` and `{{ chapter }}` depend on the page being
+/// laid out. Other expressions are evaluated like those in the body, filters
+/// included, except that an undefined variable is left out so that a theme
+/// works for documents without a `title` or `author`.
+fn marginal_template(template: &str, vars: &Variables) -> Result<String> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+
+    let literal = |text: &str| format!("\"{}\"", escape_typst_string(text));
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else {
+            break;
+        };
+        if start > 0 {
+            parts.push(literal(&rest[..start]));
+        }
+        let expr = &rest[start + 2..start + len];
+        let value = match expr.trim() {
+            "page" => Some("counter(page).display()".to_string()),
+            "total" => Some("str(counter(page).final().first())".to_string()),
+            "chapter" => Some(CURRENT_CHAPTER.to_string()),
+            _ => evaluate_optional(expr, vars)
+                .map_err(|message| {
+                    Md2PdfError::Theme(format!(
+                        "in header or footer template `{template}`: {message}"
+                    ))
+                })?
+                .as_deref()
+                .map(literal),
+        };
+        parts.extend(value);
+        rest = &rest[start + len + 2..];
+    }
+    if !rest.is_empty() {
+        parts.push(literal(rest));
+    }
+
+    Ok(format!("{{ {} }}", parts.join("; ")))
+}
+
+/// The level 1 heading that starts on the current page, or else the last
+/// one before it
+const CURRENT_CHAPTER: &str = "{ let here-page = here().page(); let on-page = query(heading.where(level: 1)).filter(h => h.location().page() == here-page); let before = query(heading.where(level: 1).before(here())); if on-page.len() > 0 { on-page.first().body } else if before.len() > 0 { before.last().body } }";
+
+fn lang_rule(lang: &str) -> String {
+    match split_lang(lang) {
+        Some((language, Some(region))) => {
+            format!("#set text(lang: \"{language}\", region: \"{region}\")\n")
+        }
+        Some((language, None)) => format!("#set text(lang: \"{language}\")\n"),
+        // Checked when the frontmatter is resolved
+        None => String::new(),
     }
 }
 
+/// A heading anchor as GitHub and mdBook generate them: lowercase, with
+/// spaces as hyphens and punctuation removed
+pub(crate) fn slugify(text: &str) -> String {
+    let slug: String = text
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' | '-' => Some('-'),
+            c if c.is_alphanumeric() || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect();
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+/// Resolve `.` and `..` in a relative path without touching the file system
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut parts: Vec<Component<'_>> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(parts.last(), Some(Component::Normal(_))) => {
+                parts.pop();
+            }
+            component => parts.push(component),
+        }
+    }
+    parts.iter().collect()
+}
+
 fn escape_typst_text(s: &str) -> String {
     s.replace('\\', "\\\\")
         .replace('#', "\\#")
@@ -372,20 +1291,37 @@ fn escape_typst_text(s: &str) -> String {
         .replace('>', "\\>")
 }
 
-fn escape_typst_string(s: &str) -> String {
+/// Inline code as raw text; a backtick inside would end the usual
+/// `` `code` `` early, so such code goes in a string instead
+fn code_span(code: &str) -> String {
+    if code.contains('`') {
+        format!("#raw(\"{}\");", escape_typst_string(code))
+    } else {
+        format!("`{code}`")
+    }
+}
+
+/// A code block fence longer than any run of backticks in `content`
+fn fence(content: &str) -> String {
+    let longest = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+pub(crate) fn escape_typst_string(s: &str) -> String {
     s.replace('\\', "\\\\").replace('"', "\\\"")
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Style;
     use crate::parser::markdown::parse_markdown;
 
     fn convert_md(md: &str) -> String {
         let config = Config::default();
         let events = parse_markdown(md);
         let fm = Frontmatter::default();
-        to_typst(events, &fm, &config)
+        to_typst(events, &fm, &config).unwrap().output
     }
 
     #[test]
@@ -418,10 +1354,347 @@ mod tests {
         assert!(result.contains("`code`"));
     }
 
+    #[test]
+    fn test_image_alt_text() {
+        let result = convert_md("![A \"quoted\" diagram](img/flow.png)");
+        assert!(result.contains(r#"#image("img/flow.png", alt: "A \"quoted\" diagram")"#));
+        assert!(!result.contains("diagram]"));
+    }
+
+    #[test]
+    fn test_document_metadata() {
+        let fm = Frontmatter {
+            title: Some("Report".to_string()),
+            keywords: vec!["a".to_string()],
+            ..Default::default()
+        };
+        let result = to_typst(parse_markdown("Body"), &fm, &Config::default())
+            .unwrap()
+            .output;
+        assert!(result.contains(r#"#set document(title: "Report", keywords: ("a",))"#));
+    }
+
+    #[test]
+    fn test_lang() {
+        let fm = Frontmatter {
+            lang: Some("pt_BR".to_string()),
+            ..Default::default()
+        };
+        let result = to_typst(parse_markdown("Olá"), &fm, &Config::default())
+            .unwrap()
+            .output;
+        assert!(result.contains(r#"#set text(lang: "pt", region: "BR")"#));
+    }
+
+    #[test]
+    fn test_chapter_headings_and_links() {
+        let chapters = HashMap::from([
+            (PathBuf::from("intro.md"), "intro".to_string()),
+            (PathBuf::from("guide/setup.md"), "guide-setup".to_string()),
+        ]);
+        let chapter = ChapterContext {
+            heading_offset: 1,
+            label: "guide-setup".to_string(),
+            dir: PathBuf::from("guide"),
+            chapters: &chapters,
+        };
+        let md = "# Install it\n\n## Install it\n\nSee [intro](../intro.md), [below](#install-it) and ![map](img/map.png)";
+        let result = chapter_to_typst(parse_markdown(md), &chapter, &Config::default()).output;
+
+        assert!(result.starts_with("#metadata(none) <guide-setup>"));
+        assert!(result.contains("== Install it <guide-setup:install-it>"));
+        assert!(result.contains("=== Install it <guide-setup:install-it-1>"));
+        assert!(result.contains("#link(<intro>)[intro]"));
+        assert!(result.contains("#link(<guide-setup:install-it>)[below]"));
+        assert!(result.contains(r#"#image("guide/img/map.png", alt: "map")"#));
+    }
+
+    #[test]
+    fn test_marginals_rule() {
+        let fm = Frontmatter {
+            title: Some("Report".to_string()),
+            ..Default::default()
+        };
+        let header = Marginal {
+            left: Some("{{ title }}".to_string()),
+            even_right: Some("{{ chapter }}".to_string()),
+            first_page: Some(false),
+            ..Default::default()
+        };
+        let footer = Marginal {
+            center: Some("{{ page }} / {{ total }}".to_string()),
+            ..Default::default()
+        };
+
+        let rule = marginals_rule(Some(&header), Some(&footer), &fm).unwrap();
+        assert!(rule.starts_with("#set page(header: context { if counter(page).get().first() == 1 { none } else { if calc.even(here().page())"));
+        assert!(rule.contains("align(left, { \"Report\" })"));
+        assert!(rule.contains("footer: context { grid("));
+        assert!(
+            rule.contains("counter(page).display(); \" / \"; str(counter(page).final().first())")
+        );
+
+        assert_eq!(
+            marginals_rule(None, Some(&Marginal::default()), &fm).unwrap(),
+            ""
+        );
+
+        let fm = Frontmatter {
+            extra: serde_yaml::from_str("version: 2.1").unwrap(),
+            ..fm
+        };
+        let filtered = Marginal {
+            left: Some("{{ title | upper }} v{{ version }}{{ author }}".to_string()),
+            right: Some("{{ author | default('Anonymous') }}".to_string()),
+            ..Default::default()
+        };
+        let rule = marginals_rule(Some(&filtered), None, &fm).unwrap();
+        assert!(rule.contains("align(left, { \"REPORT\"; \" v\"; \"2.1\" })"));
+        assert!(rule.contains("align(right, { \"Anonymous\" })"));
+
+        let unknown = Marginal {
+            left: Some("{{ title | shout }}".to_string()),
+            ..Default::default()
+        };
+        let err = marginals_rule(Some(&unknown), None, &fm).unwrap_err();
+        assert!(err.to_string().contains("unknown filter `shout`"), "{err}");
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Getting Started!"), "getting-started");
+        assert_eq!(slugify("`Config` & options"), "config--options");
+    }
+
     #[test]
     fn test_list() {
         let result = convert_md("- item 1\n- item 2");
         assert!(result.contains("- item 1"));
         assert!(result.contains("- item 2"));
     }
+
+    #[test]
+    fn test_columns() {
+        let config = Config {
+            style: Style {
+                columns: Some(2),
+                ..Style::default()
+            },
+            ..Config::default()
+        };
+        let markdown =
+            "One\n\n<!-- column-break -->\n\nTwo\n\n<!-- span -->\n\n| a |\n|---|\n| 1 |\n";
+        let fm = Frontmatter {
+            title: Some("News".to_string()),
+            ..Frontmatter::default()
+        };
+        let result = to_typst(parse_markdown(markdown), &fm, &config)
+            .unwrap()
+            .output;
+
+        assert!(
+            result.contains("#set page(paper: \"a4\", margin: (x: 2.5cm, y: 2.5cm), columns: 2)")
+        );
+        assert!(result.contains(
+            "#place(top, float: true, scope: \"parent\", clearance: 2em)[\n#align(center"
+        ));
+        assert!(result.contains("One\n\n\n#colbreak()\n"));
+        assert!(result.contains(&format!("{SPAN}[\n#table(")));
+        assert!(result.ends_with("  [1],\n)\n]\n"));
+        // Comments are otherwise dropped, and images span only when marked
+        assert!(!convert_md("<!-- note -->\n![a](a.png)").contains("place"));
+        assert!(convert_md("<!-- span -->\n\n![a](a.png)").contains(&format!("{SPAN}[#image")));
+        assert!(convert_md("Text <!-- span -->\n\n| a |\n|---|\n| 1 |\n").contains(SPAN));
+        // Only the very next block
+        for markdown in [
+            "<!-- span -->\n\nText\n\n| a |\n|---|\n| 1 |\n",
+            "<!-- span -->\n\n# Heading\n\n![a](a.png)",
+            "<!-- span -->\n\n- item\n\n![a](a.png)",
+        ] {
+            assert!(!convert_md(markdown).contains(SPAN), "{markdown}");
+        }
+    }
+
+    #[test]
+    fn test_cover_page() {
+        let mut extra = std::collections::BTreeMap::new();
+        for (key, value) in [
+            ("subtitle", serde_yaml::Value::from("Q3 \"final\"")),
+            ("version", serde_yaml::Value::from(2.1)),
+            ("organisation", serde_yaml::Value::from("Acme")),
+            ("cover_background", serde_yaml::Value::from("#eeeeee")),
+        ] {
+            extra.insert(key.to_string(), value);
+        }
+        let fm = Frontmatter {
+            title: Some("Report".to_string()),
+            cover: Some("classic".to_string()),
+            extra,
+            ..Frontmatter::default()
+        };
+        let result = to_typst(parse_markdown("Body"), &fm, &Config::default())
+            .unwrap()
+            .output;
+
+        assert!(result.contains(
+            "#let cover = (\n  title: \"Report\",\n  subtitle: \"Q3 \\\"final\\\"\",\n  author: none,"
+        ));
+        assert!(result.contains("  version: \"2.1\",\n  organization: \"Acme\","));
+        assert!(result.contains("  background: rgb(\"#eeeeee\"),\n)\n"));
+        assert!(result.contains("]\n#counter(page).update(1)\n\nBody"));
+        // The cover takes the place of the title block
+        assert!(!result.contains("text(size: 24pt"));
+
+        let fm = Frontmatter {
+            cover: Some("fancy".to_string()),
+            ..Frontmatter::default()
+        };
+        let err = to_typst(parse_markdown("Body"), &fm, &Config::default()).unwrap_err();
+        assert!(err.to_string().contains("unknown cover `fancy`"));
+    }
+
+    #[test]
+    fn test_html() {
+        let result = convert_md(
+            "H<sub>2</sub>O, <kbd>Ctrl</kbd><br>\n<code>a \"b\"</code>\n\n<p align=\"center\">\n<img src=\"logo.png\" width=\"200\" alt=\"Logo\">\n</p>\n\n<details>\n<summary>More &amp; more</summary>\n\nHidden\n\n</details>\n\n<pre>\nx < y\n</pre>\n",
+        );
+        assert!(result.contains("H#sub[2]O, #box("));
+        assert!(result.contains("[Ctrl]#linebreak()"));
+        assert!(result.contains("#raw(\"a \\\"b\\\"\")"));
+        assert!(result
+            .contains("#align(center)[\n\n#image(\"logo.png\", width: 150pt, alt: \"Logo\")\n]"));
+        assert!(result.contains("#block[\n\n#strong[More & more]"));
+        assert!(result.contains("Hidden\n\n]"));
+        assert!(result.contains("#raw(block: true, \"x < y\n\")"));
+    }
+
+    #[test]
+    fn test_unclosed_html_ends_with_its_container() {
+        let result = convert_md("| <b>x | y |\n|---|---|\n| <i>1 | 2 |\n");
+        assert!(result.contains("  [*#strong[x]*],\n  [*y*],\n  [#emph[1]],\n  [2],\n"));
+
+        let result = convert_md("# <b>Title\n\nSome <i>text\n\nMore</i> text\n");
+        assert!(result.contains("= #strong[Title]\n"));
+        assert!(result.contains("Some #emph[text]\n\nMore text"));
+    }
+
+    #[test]
+    fn test_html_table() {
+        let result = convert_md(
+            "<table>\n<tr><th>Name</th><th>Value</th></tr>\n<tr><td><b>x</b></td><td>1</td></tr>\n</table>\n",
+        );
+        assert!(result.contains(
+            "#table(\n  columns: 2,\n  [*Name*],\n  [*Value*],\n  [#strong[x]],\n  [1],\n)"
+        ));
+    }
+
+    #[test]
+    fn test_inline_markup_in_html_table() {
+        let result = convert_md(
+            "Text <table><tr><td>a **bold** ~~x~~ b</td><td>`code` $x$</td></tr></table>\n",
+        );
+        assert!(result.contains("Text "));
+        assert!(result.contains("  [a *bold* #strike[x] b],\n  [`code` $x$],\n"));
+        assert!(!result.contains("Text *"));
+    }
+
+    #[test]
+    fn test_unsupported_html() {
+        let mut config = Config::default();
+        let markdown = "<font color=\"red\">red</font> text";
+        let result = to_typst(parse_markdown(markdown), &Frontmatter::default(), &config).unwrap();
+        assert!(result.output.contains("red text"));
+        assert_eq!(result.warnings, ["unsupported HTML `<font>` left out"]);
+
+        config.keep_html = true;
+        let result = to_typst(parse_markdown(markdown), &Frontmatter::default(), &config)
+            .unwrap()
+            .output;
+        assert!(result.contains("\\<font color=\"red\"\\>red\\</font\\> text"));
+    }
+
+    #[test]
+    fn test_raw_typst() {
+        let markdown = "```{=typst}\n#let x = 1\n```\n\nA `#box[b]`{=typst}, `c` and <!--typst #text(red)[d] -->\n\n<!--typst\n#pagebreak()\n-->\n";
+        let result = convert_md(markdown);
+        assert!(result.contains("\n#let x = 1\n\n"));
+        assert!(result.contains("A #box[b], `c` and #text(red)[d]"));
+        assert!(result.contains("\n#pagebreak()"));
+
+        let config = Config {
+            raw_typst: false,
+            ..Config::default()
+        };
+        let result = to_typst(parse_markdown(markdown), &Frontmatter::default(), &config).unwrap();
+        assert!(result.output.contains("```typ\n#let x = 1\n```"));
+        assert!(result.output.contains("A `#box[b]`, `c` and \n"));
+        assert!(!result.output.contains("#pagebreak()"));
+        assert_eq!(result.warnings.len(), 3);
+    }
+
+    fn convert_untrusted(markdown: &str) -> String {
+        let config = Config {
+            raw_typst: false,
+            ..Config::default()
+        };
+        to_typst(parse_markdown(markdown), &Frontmatter::default(), &config)
+            .unwrap()
+            .output
+    }
+
+    #[test]
+    fn test_backticks_in_code_spans() {
+        let result = convert_untrusted("`` a` #read(\"x.txt\") `b ``(c)");
+        assert!(result.contains("#raw(\"a` #read(\\\"x.txt\\\") `b\");(c)"));
+        assert!(convert_untrusted("`a`").contains("`a`"));
+    }
+
+    #[test]
+    fn test_backticks_in_code_blocks() {
+        let result = convert_untrusted("~~~\n```\n#read(\"x.txt\")\n```\n~~~\n");
+        assert!(result.contains("\n````\n```\n#read(\"x.txt\")\n```\n````\n"));
+    }
+
+    #[test]
+    fn test_no_raw_typst_escapes_code_in_math() {
+        let result = convert_untrusted("$#read(\"x.txt\") + \\#1$");
+        assert!(result.contains("$\\#read(\"x.txt\") + \\#1$"));
+        assert!(convert_md("$#h(1em)$").contains("$#h(1em)$"));
+    }
+
+    #[test]
+    fn test_layout_directives() {
+        let result = convert_md(
+            "A\n\n\\newpage\n\n<!-- landscape start -->\n\n<!-- keep-together -->\n\nB <!-- vspace 1cm --> C\n\n<!-- landscape end -->\n\n<div style=\"page-break-after: always\"></div>\n\n<!-- keep-together -->\n",
+        );
+        assert!(result.contains("A\n\n\n#pagebreak()\n"));
+        assert!(result.contains("#page(paper: \"a4\", flipped: true)[\n"));
+        assert!(result.contains("#block(breakable: false)[\n"));
+        assert!(result.contains("B \n#v(1cm)\n C"));
+        // Closing the landscape section closes the block inside it
+        assert!(result.contains("C\n\n\n]\n\n]\n\n\n#pagebreak()\n"));
+        // Sections left open are closed at the end
+        assert!(result.ends_with("#block(breakable: false)[\n\n\n]\n"));
+    }
+
+    #[test]
+    fn test_directives_in_tables() {
+        let config = Config::default();
+        let markdown = "| x <!-- pagebreak --> | y |\n|---|---|\n| <!-- landscape start --> 1 | 2 <!-- vspace 1cm --> |\n\n<table><tr><td>a <!-- column-break --></td></tr></table>\n";
+        let result = to_typst(parse_markdown(markdown), &Frontmatter::default(), &config).unwrap();
+        let warnings = result.warnings;
+        let result = result.output;
+        assert!(result.contains("  [*x *],\n  [*y*],\n  [ 1],\n  [2 \n#v(1cm)\n],\n"));
+        assert!(!result.contains("#pagebreak()"));
+        assert!(!result.contains("#page("));
+        assert!(!result.contains("#colbreak()"));
+        assert_eq!(
+            warnings,
+            [
+                "page break in a table left out",
+                "landscape section in a table left out",
+                "column break in a table left out"
+            ]
+        );
+    }
 }
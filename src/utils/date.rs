@@ -0,0 +1,171 @@
+use chrono::{DateTime, Days, Local, Locale, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use std::fmt::Write;
+
+/// Build date: the date of `SOURCE_DATE_EPOCH` or the local date, and `None`
+/// for a reproducible build without `SOURCE_DATE_EPOCH`
+pub fn today(reproducible: bool) -> Option<NaiveDate> {
+    match source_date_epoch() {
+        Some(time) => Some(time.date_naive()),
+        None if reproducible => None,
+        None => Some(Local::now().date_naive()),
+    }
+}
+
+/// Build date at a fixed UTC offset in hours, as Typst's `datetime.today` asks for
+pub fn today_with_offset(reproducible: bool, hours: i64) -> Option<NaiveDate> {
+    let now = build_time(reproducible)?;
+    Some((now + TimeDelta::try_hours(hours)?).date_naive())
+}
+
+/// The build time: `SOURCE_DATE_EPOCH` when set, otherwise the clock, and
+/// `None` for a reproducible build, which must not depend on the clock
+pub fn build_time(reproducible: bool) -> Option<DateTime<Utc>> {
+    source_date_epoch().or_else(|| (!reproducible).then(Utc::now))
+}
+
+/// The `SOURCE_DATE_EPOCH` environment variable, used for reproducible builds
+pub fn source_date_epoch() -> Option<DateTime<Utc>> {
+    let seconds = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    DateTime::from_timestamp(seconds, 0)
+}
+
+/// Resolve `today`, `yesterday`, `tomorrow` and offsets like `today+7d` or
+/// `today-2w` against `today`. Returns `None` for anything else.
+pub fn resolve_relative(value: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let value = value.trim().to_lowercase();
+    match value.as_str() {
+        "today" => return Some(today),
+        "yesterday" => return today.checked_sub_days(Days::new(1)),
+        "tomorrow" => return today.checked_add_days(Days::new(1)),
+        _ => {}
+    }
+
+    let offset = value.strip_prefix("today")?.replace(' ', "");
+    let (negative, amount) = match offset.split_at_checked(1)? {
+        ("+", amount) => (false, amount),
+        ("-", amount) => (true, amount),
+        _ => return None,
+    };
+    let days = if let Some(count) = amount.strip_suffix('d') {
+        count.parse::<u64>().ok()?
+    } else if let Some(count) = amount.strip_suffix('w') {
+        count.parse::<u64>().ok()?.checked_mul(7)?
+    } else {
+        return None;
+    };
+
+    if negative {
+        today.checked_sub_days(Days::new(days))
+    } else {
+        today.checked_add_days(Days::new(days))
+    }
+}
+
+/// Parse an ISO 8601 date or date-time as written in frontmatter
+pub fn parse_date(value: &str) -> Option<NaiveDateTime> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|d| d.naive_local())
+        })
+        .or_else(|| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").ok())
+}
+
+/// Format with a strftime pattern, using month and day names for `lang`
+///
+/// Returns `None` if the pattern is invalid.
+pub fn format_date(datetime: NaiveDateTime, format: &str, lang: Option<&str>) -> Option<String> {
+    let datetime = Utc.from_utc_datetime(&datetime);
+    let locale = lang.and_then(locale).unwrap_or(Locale::POSIX);
+
+    let mut out = String::new();
+    write!(out, "{}", datetime.format_localized(format, locale)).ok()?;
+    Some(out)
+}
+
+/// Map a language tag (`fr`, `pt-BR`, `de_AT`) to a chrono locale
+fn locale(lang: &str) -> Option<Locale> {
+    let lang = lang.replace('-', "_");
+    let (language, region) = match lang.split_once('_') {
+        Some((language, region)) => (language.to_lowercase(), region.to_uppercase()),
+        None => {
+            let language = lang.to_lowercase();
+            let region = match language.as_str() {
+                "en" => "US",
+                "ja" => "JP",
+                "zh" => "CN",
+                "sv" => "SE",
+                "da" => "DK",
+                "el" => "GR",
+                "ko" => "KR",
+                "cs" => "CZ",
+                "uk" => "UA",
+                "nb" | "nn" => "NO",
+                "ca" => "ES",
+                "et" => "EE",
+                "sl" => "SI",
+                "vi" => "VN",
+                "he" => "IL",
+                "hi" => "IN",
+                "ar" => "SA",
+                "fa" => "IR",
+                "ga" => "IE",
+                "sr" => "RS",
+                _ => "",
+            };
+            let region = if region.is_empty() {
+                language.to_uppercase()
+            } else {
+                region.to_string()
+            };
+            (language, region)
+        }
+    };
+
+    Locale::try_from(format!("{language}_{region}").as_str()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_relative() {
+        let today = NaiveDate::from_ymd_opt(2025, 1, 21).unwrap();
+        assert_eq!(resolve_relative("today", today), Some(today));
+        assert_eq!(
+            resolve_relative("yesterday", today),
+            NaiveDate::from_ymd_opt(2025, 1, 20)
+        );
+        assert_eq!(
+            resolve_relative("today + 2w", today),
+            NaiveDate::from_ymd_opt(2025, 2, 4)
+        );
+        assert_eq!(resolve_relative("2025-01-21", today), None);
+    }
+
+    #[test]
+    fn test_format_localized() {
+        let date = parse_date("2025-03-05").unwrap();
+        assert_eq!(
+            format_date(date, "%-d %B %Y", None).unwrap(),
+            "5 March 2025"
+        );
+        assert_eq!(
+            format_date(date, "%-d %B %Y", Some("fr")).unwrap(),
+            "5 mars 2025"
+        );
+        assert_eq!(
+            format_date(date, "%-d %B %Y", Some("de-AT")).unwrap(),
+            "5 März 2025"
+        );
+        assert!(format_date(date, "%Q", None).is_none());
+    }
+}
@@ -14,6 +14,24 @@ pub fn is_local_image(path: &str) -> bool {
     )
 }
 
+/// Guess the MIME type of a local image from its extension
+pub fn mime_type(path: &str) -> &'static str {
+    let ext = Path::new(path)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
 /// Check if a URL is a remote image
 pub fn is_remote_url(url: &str) -> bool {
     url.starts_with("http://") || url.starts_with("https://")
@@ -30,6 +48,12 @@ mod tests {
         assert!(!is_local_image("document.pdf"));
     }
 
+    #[test]
+    fn test_mime_type() {
+        assert_eq!(mime_type("logo.SVG"), "image/svg+xml");
+        assert_eq!(mime_type("photo.jpeg"), "image/jpeg");
+    }
+
     #[test]
     fn test_remote_url() {
         assert!(is_remote_url("https://example.com/image.png"));
@@ -1 +1,3 @@
+pub mod date;
 pub mod images;
+pub mod suggest;
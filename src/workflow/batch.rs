@@ -0,0 +1,310 @@
+use crate::{Md2PdfError, Result};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// One file to convert
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub input: PathBuf,
+    pub output: PathBuf,
+}
+
+/// The result of converting one file
+#[derive(Debug)]
+pub struct Outcome {
+    pub job: Job,
+    pub result: Result<()>,
+    pub elapsed: Duration,
+}
+
+/// Whether a command-line input is a glob pattern rather than a path
+pub fn is_pattern(input: &Path) -> bool {
+    input.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// Expand files, directories and glob patterns into conversion jobs
+///
+/// Directories are searched recursively for `.md` and `.markdown` files. With
+/// `out_dir`, outputs mirror each file's path below its directory or the
+/// fixed part of its pattern; without it, they sit next to the inputs. A file
+/// that does not exist still gets a job, so it is reported as failed along
+/// with the others; two inputs that would write the same output are an error.
+pub fn plan(inputs: &[PathBuf], out_dir: Option<&Path>, extension: &str) -> Result<Vec<Job>> {
+    let mut jobs: Vec<Job> = Vec::new();
+
+    for input in inputs {
+        let (root, files) = if is_pattern(input) {
+            (pattern_root(input), expand_pattern(input)?)
+        } else if input.is_dir() {
+            let mut files = Vec::new();
+            find_markdown(input, &mut files)?;
+            (input.clone(), files)
+        } else {
+            let root = input.parent().map(Path::to_path_buf).unwrap_or_default();
+            (root, vec![input.clone()])
+        };
+
+        if files.is_empty() {
+            return Err(Md2PdfError::InvalidInput(format!(
+                "no Markdown files found for `{}`",
+                input.display()
+            )));
+        }
+
+        for file in files {
+            if jobs.iter().any(|job| job.input == file) {
+                continue;
+            }
+            let output = match out_dir {
+                Some(out_dir) => {
+                    let relative = file.strip_prefix(&root).unwrap_or(&file);
+                    out_dir.join(relative).with_extension(extension)
+                }
+                None => file.with_extension(extension),
+            };
+            if let Some(other) = jobs.iter().find(|job| job.output == output) {
+                return Err(Md2PdfError::InvalidInput(format!(
+                    "`{}` and `{}` would both be written to `{}`",
+                    other.input.display(),
+                    file.display(),
+                    output.display()
+                )));
+            }
+            jobs.push(Job {
+                input: file,
+                output,
+            });
+        }
+    }
+
+    Ok(jobs)
+}
+
+/// Run `convert` for every job on up to `threads` worker threads
+///
+/// Outcomes come back in job order; a failing job does not stop the others.
+pub fn run<F>(jobs: &[Job], threads: usize, convert: F) -> Vec<Outcome>
+where
+    F: Fn(&Job) -> Result<()> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let outcomes = Mutex::new(Vec::with_capacity(jobs.len()));
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, jobs.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(job) = jobs.get(index) else {
+                    break;
+                };
+
+                let start = Instant::now();
+                let result = create_parent(&job.output).and_then(|()| convert(job));
+                let outcome = Outcome {
+                    job: job.clone(),
+                    result,
+                    elapsed: start.elapsed(),
+                };
+                outcomes
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push((index, outcome));
+            });
+        }
+    });
+
+    let mut outcomes = outcomes.into_inner().unwrap_or_else(|e| e.into_inner());
+    outcomes.sort_by_key(|(index, _)| *index);
+    outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+}
+
+/// A table of every file's status and timing, followed by totals
+pub fn summary(outcomes: &[Outcome]) -> String {
+    let mut table = String::new();
+    let _ = writeln!(table, "{:<7} {:>8}  File", "Status", "Time");
+
+    for outcome in outcomes {
+        let time = format!("{:.2}s", outcome.elapsed.as_secs_f64());
+        let input = outcome.job.input.display();
+        let _ = match &outcome.result {
+            Ok(()) => writeln!(
+                table,
+                "{:<7} {:>8}  {} -> {}",
+                "ok",
+                time,
+                input,
+                outcome.job.output.display()
+            ),
+            Err(e) => writeln!(table, "{:<7} {:>8}  {}: {}", "FAILED", time, input, e),
+        };
+    }
+
+    let failed = outcomes.iter().filter(|o| o.result.is_err()).count();
+    let _ = write!(
+        table,
+        "{} converted, {} failed",
+        outcomes.len() - failed,
+        failed
+    );
+    table
+}
+
+/// The leading components of a pattern that contain no wildcards
+fn pattern_root(pattern: &Path) -> PathBuf {
+    pattern
+        .components()
+        .take_while(|c| !is_pattern(Path::new(c.as_os_str())))
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+fn expand_pattern(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let pattern = pattern.to_string_lossy();
+    // Like directory inputs, skip hidden files unless the pattern names them
+    let options = glob::MatchOptions {
+        require_literal_leading_dot: true,
+        ..Default::default()
+    };
+    let paths = glob::glob_with(&pattern, options)
+        .map_err(|e| Md2PdfError::InvalidInput(format!("invalid pattern `{pattern}`: {e}")))?;
+
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.map_err(|e| Md2PdfError::Io(e.into()))?;
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Markdown files below `dir` in sorted order, skipping hidden entries
+fn find_markdown(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries: Vec<_> = fs::read_dir(dir)?
+        .collect::<std::io::Result<Vec<_>>>()?
+        .into_iter()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            !path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        })
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            find_markdown(&path, files)?;
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext == "md" || ext == "markdown")
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn create_parent(output: &Path) -> Result<()> {
+    match output.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => Ok(fs::create_dir_all(parent)?),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("docs/guide")).unwrap();
+        fs::create_dir_all(dir.path().join("docs/.hidden")).unwrap();
+        for file in [
+            "docs/index.md",
+            "docs/guide/setup.md",
+            "docs/guide/notes.txt",
+            "docs/.hidden/skip.md",
+        ] {
+            fs::write(dir.path().join(file), "# Doc\n").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_plan_mirrors_directories() {
+        let dir = tree();
+        let docs = dir.path().join("docs");
+        let out = dir.path().join("build");
+
+        let jobs = plan(std::slice::from_ref(&docs), Some(&out), "pdf").unwrap();
+        let outputs: Vec<_> = jobs.iter().map(|job| job.output.clone()).collect();
+        assert_eq!(
+            outputs,
+            vec![out.join("guide/setup.pdf"), out.join("index.pdf")]
+        );
+
+        let pattern = docs.join("**/*.md");
+        let jobs = plan(&[pattern, docs.join("index.md")], Some(&out), "pdf").unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert!(jobs
+            .iter()
+            .any(|job| job.output == out.join("guide/setup.pdf")));
+    }
+
+    #[test]
+    fn test_plan_keeps_missing_files_and_rejects_empty_patterns() {
+        let dir = tree();
+        let missing = dir.path().join("missing.md");
+        let jobs = plan(&[missing.clone(), dir.path().join("docs")], None, "pdf").unwrap();
+        assert_eq!(jobs.len(), 3);
+        assert_eq!(jobs[0].input, missing);
+
+        assert!(plan(&[dir.path().join("docs/*.rst")], None, "pdf").is_err());
+    }
+
+    #[test]
+    fn test_plan_rejects_clashing_outputs() {
+        let dir = tree();
+        fs::write(dir.path().join("index.md"), "# Other\n").unwrap();
+        let inputs = [
+            dir.path().join("index.md"),
+            dir.path().join("docs/index.md"),
+        ];
+
+        let err = plan(&inputs, Some(&dir.path().join("build")), "pdf")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("would both be written to"), "{err}");
+
+        // Next to the inputs, they do not clash
+        assert_eq!(plan(&inputs, None, "pdf").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_run_keeps_order_and_failures() {
+        let jobs: Vec<Job> = (0..5)
+            .map(|i| Job {
+                input: PathBuf::from(format!("{i}.md")),
+                output: PathBuf::from(format!("{i}.pdf")),
+            })
+            .collect();
+
+        let outcomes = run(&jobs, 3, |job| {
+            if job.input == Path::new("3.md") {
+                Err(Md2PdfError::InvalidInput("broken".to_string()))
+            } else {
+                Ok(())
+            }
+        });
+
+        assert_eq!(outcomes.len(), 5);
+        assert!(outcomes.iter().enumerate().all(|(i, o)| o.job == jobs[i]));
+        assert!(outcomes[3].result.is_err());
+        assert!(summary(&outcomes).ends_with("4 converted, 1 failed"));
+    }
+}
@@ -0,0 +1,260 @@
+use super::watch::Watcher;
+use crate::config::OutputFormat;
+use crate::renderer::Rendered;
+use crate::Result;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How often an idle event stream sends a comment to detect closed browsers
+const KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// The latest build, shared between the build thread and connections
+#[derive(Default)]
+struct Preview {
+    /// Incremented after every build, successful or not
+    version: u64,
+    pages: Vec<Vec<u8>>,
+    error: Option<String>,
+}
+
+type Shared = Arc<(Mutex<Preview>, Condvar)>;
+
+/// A live-preview server on the loopback interface
+///
+/// Pages are rendered in the watcher's format, SVG or PNG, and the browser
+/// reloads over server-sent events after every rebuild.
+pub struct Server {
+    listener: TcpListener,
+}
+
+impl Server {
+    /// Bind to `127.0.0.1:port`; port 0 picks a free port
+    pub fn bind(port: u16) -> Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind((Ipv4Addr::LOCALHOST, port))?,
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Serve pages built by `watcher` until the process exits
+    pub fn run(self, mut watcher: Watcher) -> Result<()> {
+        let format = watcher.format();
+        let shared: Shared = Arc::default();
+
+        let builder = Arc::clone(&shared);
+        thread::spawn(move || loop {
+            let result = watcher.build().map(|converted| {
+                for warning in converted.warnings {
+                    eprintln!("Warning: {warning}");
+                }
+                converted.output
+            });
+            let (lock, changed) = &*builder;
+            let mut preview = lock.lock().unwrap_or_else(|e| e.into_inner());
+            match result {
+                Ok(Rendered::Pages(pages)) => {
+                    preview.pages = pages.into_iter().map(|(_, data)| data).collect();
+                    preview.error = None;
+                }
+                Ok(Rendered::Document(_)) => {
+                    preview.error = Some("previews need PNG or SVG output".to_string());
+                }
+                // Keep showing the last good pages under the error
+                Err(e) => preview.error = Some(e.to_string()),
+            }
+            preview.version += 1;
+            changed.notify_all();
+            drop(preview);
+
+            watcher.wait_for_change();
+        });
+
+        for stream in self.listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                // A browser that disconnects mid-response is not an error
+                let _ = handle(stream, &shared, format);
+            });
+        }
+        Ok(())
+    }
+}
+
+fn handle(mut stream: TcpStream, shared: &Shared, format: OutputFormat) -> std::io::Result<()> {
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let path = target.split('?').next().unwrap_or("");
+
+    if method != "GET" {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"");
+    }
+
+    let (lock, changed) = &**shared;
+    match path {
+        "/" => {
+            let page = index(&lock.lock().unwrap_or_else(|e| e.into_inner()));
+            respond(
+                &mut stream,
+                "200 OK",
+                "text/html; charset=utf-8",
+                page.as_bytes(),
+            )
+        }
+        "/events" => {
+            stream.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n",
+            )?;
+            let mut seen = lock.lock().unwrap_or_else(|e| e.into_inner()).version;
+            loop {
+                let preview = lock.lock().unwrap_or_else(|e| e.into_inner());
+                let (preview, _) = changed
+                    .wait_timeout_while(preview, KEEPALIVE, |p| p.version == seen)
+                    .unwrap_or_else(|e| e.into_inner());
+                let message = if preview.version == seen {
+                    ": keepalive\n\n".to_string()
+                } else {
+                    seen = preview.version;
+                    format!("data: {seen}\n\n")
+                };
+                drop(preview);
+                stream.write_all(message.as_bytes())?;
+                stream.flush()?;
+            }
+        }
+        _ => {
+            let page = path
+                .strip_prefix("/page/")
+                .and_then(|n| n.parse::<usize>().ok())
+                .and_then(|n| {
+                    let preview = lock.lock().unwrap_or_else(|e| e.into_inner());
+                    preview.pages.get(n.checked_sub(1)?).cloned()
+                });
+            match page {
+                Some(data) => respond(&mut stream, "200 OK", mime(format), &data),
+                None => respond(&mut stream, "404 Not Found", "text/plain", b"not found"),
+            }
+        }
+    }
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+fn mime(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Png => "image/png",
+        _ => "image/svg+xml",
+    }
+}
+
+/// The preview page: every page as an image, any build error above them
+fn index(preview: &Preview) -> String {
+    let mut body = String::new();
+    if preview.version == 0 {
+        body.push_str("<p class=\"status\">Building…</p>\n");
+    }
+    if let Some(error) = &preview.error {
+        body.push_str(&format!(
+            "<pre class=\"error\">{}</pre>\n",
+            escape_html(error)
+        ));
+    }
+    for number in 1..=preview.pages.len() {
+        body.push_str(&format!(
+            "<img src=\"/page/{number}?v={}\" alt=\"Page {number}\">\n",
+            preview.version
+        ));
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>md2pdf preview</title>
+<style>
+body {{ background: #525659; margin: 0; padding: 1rem; }}
+img {{ display: block; margin: 0 auto 1rem; max-width: 100%; background: white; box-shadow: 0 2px 8px rgba(0, 0, 0, 0.4); }}
+.error {{ background: #fdecea; color: #611a15; padding: 1rem; white-space: pre-wrap; }}
+.status {{ color: white; text-align: center; }}
+</style>
+</head>
+<body>
+{body}<script>
+new EventSource("/events").onmessage = () => location.reload();
+</script>
+</body>
+</html>
+"#
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::io::Read;
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serves_preview_page() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("doc.md");
+        std::fs::write(&input, "# Preview\n").unwrap();
+
+        let config = Config {
+            format: OutputFormat::Svg,
+            ..Config::default()
+        };
+        let server = Server::bind(0).unwrap();
+        let addr = server.local_addr().unwrap();
+        assert!(addr.ip().is_loopback());
+        thread::spawn(move || server.run(Watcher::new(&input, &config)));
+
+        let page = get(addr, "/");
+        assert!(page.starts_with("HTTP/1.1 200 OK"));
+        assert!(page.contains("EventSource"));
+        assert!(get(addr, "/page/99").starts_with("HTTP/1.1 404"));
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(escape_html("<a & b>"), "&lt;a &amp; b&gt;");
+    }
+}
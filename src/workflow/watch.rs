@@ -0,0 +1,189 @@
+use crate::book::Book;
+use crate::config::{Config, OutputFormat};
+use crate::renderer::{Rendered, Session};
+use crate::{file_context, prepare_file, Converted, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How often watched files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long files must stay unchanged before a rebuild, so that a burst of
+/// saves triggers a single build
+const DEBOUNCE: Duration = Duration::from_millis(150);
+
+/// Modification time and size, or `None` for a missing file
+type Stamp = Option<(SystemTime, u64)>;
+
+/// Rebuilds a Markdown file or book and waits for it or its dependencies to change
+///
+/// The Typst world is kept between builds, so rebuilds are incremental.
+pub struct Watcher {
+    input: PathBuf,
+    name: String,
+    config: Config,
+    session: Session,
+    /// Chapters of a book, read by the last build
+    sources: Vec<PathBuf>,
+    /// Watched files with their state as of the last build
+    stamps: HashMap<PathBuf, Stamp>,
+}
+
+impl Watcher {
+    pub fn new(input: &Path, config: &Config) -> Self {
+        let (config, name) = file_context(input, config);
+        let session = Session::new(&config);
+        let stamps = HashMap::from([(input.to_path_buf(), stamp(input))]);
+        Self {
+            input: input.to_path_buf(),
+            name,
+            config,
+            session,
+            sources: Vec::new(),
+            stamps,
+        }
+    }
+
+    /// Convert the current contents of the input
+    pub fn build(&mut self) -> Result<Converted<Rendered>> {
+        // Stamp before reading, so that a save during the build is not missed
+        let before: HashMap<_, _> = self
+            .stamps
+            .keys()
+            .map(|path| (path.clone(), stamp(path)))
+            .collect();
+
+        let result = self.compile();
+
+        let mut stamps = HashMap::from([(self.input.clone(), before[&self.input])]);
+        let dependencies = self.sources.iter().cloned();
+        for path in dependencies.chain(self.session.dependencies()) {
+            let state = before.get(&path).copied().unwrap_or_else(|| stamp(&path));
+            stamps.insert(path, state);
+        }
+        // Keep watching everything if the build failed before reading dependencies
+        if result.is_err() {
+            stamps.extend(before);
+        }
+        self.stamps = stamps;
+
+        result
+    }
+
+    fn compile(&mut self) -> Result<Converted<Rendered>> {
+        let prepared = match prepare_file(&self.input, &self.name, &self.config) {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                // Watch the chapters even if one of them fails to convert
+                if let Ok(book) = Book::load(&self.input) {
+                    self.sources = book.files().collect();
+                }
+                return Err(e);
+            }
+        };
+        self.sources = prepared.sources;
+        let output = self.session.render(
+            &prepared.typst_code,
+            prepared.markdown.as_deref(),
+            &self.config,
+            &prepared.attachments,
+        )?;
+        Ok(Converted {
+            output,
+            warnings: prepared.warnings,
+        })
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.config.format
+    }
+
+    /// Files whose changes trigger a rebuild
+    pub fn watched(&self) -> impl Iterator<Item = &Path> {
+        self.stamps.keys().map(PathBuf::as_path)
+    }
+
+    /// Block until a watched file changes and then stays unchanged for a moment
+    pub fn wait_for_change(&mut self) {
+        loop {
+            if self.changed() {
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        let mut current = self.current();
+        loop {
+            thread::sleep(DEBOUNCE);
+            let next = self.current();
+            if next == current {
+                break;
+            }
+            current = next;
+        }
+    }
+
+    fn changed(&self) -> bool {
+        self.stamps
+            .iter()
+            .any(|(path, state)| stamp(path) != *state)
+    }
+
+    fn current(&self) -> HashMap<PathBuf, Stamp> {
+        self.stamps
+            .keys()
+            .map(|path| (path.clone(), stamp(path)))
+            .collect()
+    }
+}
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_for_change_after_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("doc.md");
+        fs::write(&input, "# Draft\n").unwrap();
+
+        let mut watcher = Watcher::new(&input, &Config::default());
+        let _ = watcher.build();
+        assert!(!watcher.changed());
+        assert!(watcher.watched().any(|path| path == input));
+
+        fs::write(&input, "# Draft, revised\n").unwrap();
+        assert!(watcher.changed());
+        watcher.wait_for_change();
+    }
+
+    #[test]
+    fn test_included_files_are_watched() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("book.yaml");
+        fs::write(&manifest, "chapters:\n  - intro.md\n").unwrap();
+        fs::write(
+            dir.path().join("intro.md"),
+            "# Intro\n\n{{#include note.txt}}\n",
+        )
+        .unwrap();
+        let note = dir.path().join("note.txt");
+        fs::write(&note, "A note.\n").unwrap();
+
+        let mut watcher = Watcher::new(&manifest, &Config::default());
+        let _ = watcher.build();
+        let note = note.canonicalize().unwrap();
+        assert!(watcher.watched().any(|path| path == note));
+
+        fs::write(&note, "A longer note.\n").unwrap();
+        assert!(watcher.changed());
+    }
+}
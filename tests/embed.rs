@@ -0,0 +1,55 @@
+use md2pdf_rs::{convert_file, Config};
+use std::fs;
+use std::process::Command;
+
+const SVG: &str = "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"10\" height=\"10\"/>";
+
+#[test]
+fn test_embed_source_attaches_the_markdown() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("notes.md");
+    let markdown = "---\ntitle: Notes\n---\n\nSource marker 4711.\n";
+    fs::write(&input, markdown).unwrap();
+    let output = dir.path().join("notes.pdf");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_md2pdf"))
+        .arg(&input)
+        .arg("-o")
+        .arg(&output)
+        .arg("--embed-source")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let pdf = fs::read(&output).unwrap();
+    let text = String::from_utf8_lossy(&pdf);
+    assert!(pdf.starts_with(b"%PDF-"));
+    assert!(text.contains("/EmbeddedFile"));
+    assert!(text.contains("(notes.md)"));
+    assert!(text.contains("/Subtype /text#2Fmarkdown"), "{text}");
+    assert!(text.contains(markdown));
+}
+
+#[test]
+fn test_embed_assets_stays_in_the_base_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let docs = dir.path().join("docs");
+    fs::create_dir(&docs).unwrap();
+    fs::write(docs.join("chart.svg"), SVG).unwrap();
+    fs::write(dir.path().join("secret.svg"), SVG).unwrap();
+    let config = Config {
+        embed_source: true,
+        embed_assets: true,
+        ..Config::default()
+    };
+
+    let input = docs.join("report.md");
+    fs::write(&input, "![Chart](chart.svg)\n").unwrap();
+    convert_file(&input, &docs.join("report.pdf"), &config).unwrap();
+
+    fs::write(&input, "![Chart](../secret.svg)\n").unwrap();
+    let err = convert_file(&input, &docs.join("report.pdf"), &config)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("cannot embed `../secret.svg`"), "{err}");
+}
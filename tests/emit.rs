@@ -0,0 +1,55 @@
+use md2pdf_rs::{convert_to_typst, Config};
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_convert_to_typst_includes_preamble_and_body() {
+    let markdown = "---\ntitle: Report\n---\n\n# Intro\n\nHello *world*.\n";
+    let typst = convert_to_typst(markdown, &Config::default())
+        .unwrap()
+        .output;
+
+    assert!(typst.contains("#set page("));
+    assert!(typst.contains("#set document(title: \"Report\""));
+    assert!(typst.contains("= Intro"));
+    assert!(typst.contains("_world_"));
+}
+
+#[test]
+fn test_emit_typst_writes_only_the_typst_source() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("doc.md");
+    fs::write(&input, "# Title\n").unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_md2pdf"))
+        .arg(&input)
+        .arg("--emit")
+        .arg("typst")
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let typst = fs::read_to_string(dir.path().join("doc.typ")).unwrap();
+    assert!(typst.contains("= Title"));
+    assert!(!dir.path().join("doc.pdf").exists());
+}
+
+#[test]
+fn test_unsupported_html_is_a_warning() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("doc.md");
+    fs::write(&input, "<marquee>News</marquee> and <sup>1</sup>\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_md2pdf"))
+        .arg(&input)
+        .args(["--emit", "typst"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr.matches("Warning:").count(), 1, "{stderr}");
+    assert!(stderr.contains("doc.md: unsupported HTML `<marquee>` left out"));
+    let typst = fs::read_to_string(dir.path().join("doc.typ")).unwrap();
+    assert!(typst.contains("News and #super[1]"));
+}
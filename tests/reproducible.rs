@@ -0,0 +1,78 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+/// Reads the clock through `date: today`
+const DOCUMENT: &str = "---\ntitle: Report\ndate: today\n---\n\n# Status\n\nAll good.\n";
+
+fn md2pdf(input: &Path, output: &Path, epoch: Option<&str>, args: &[&str]) -> Vec<u8> {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_md2pdf"));
+    command.arg(input).arg("-o").arg(output).args(args);
+    match epoch {
+        Some(epoch) => command.env("SOURCE_DATE_EPOCH", epoch),
+        None => command.env_remove("SOURCE_DATE_EPOCH"),
+    };
+    assert!(command.status().unwrap().success());
+    fs::read(output).unwrap()
+}
+
+#[test]
+fn test_reproducible_output_is_byte_identical() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("report.md");
+    fs::write(&input, DOCUMENT.replace("today", "2025-01-21")).unwrap();
+    let output = dir.path().join("report.pdf");
+
+    let first = md2pdf(&input, &output, None, &["--reproducible"]);
+    // A later second on the clock must not change anything
+    thread::sleep(Duration::from_millis(1100));
+    let second = md2pdf(&input, &output, None, &["--reproducible"]);
+
+    assert!(first.starts_with(b"%PDF"));
+    assert_eq!(first, second);
+    // Without SOURCE_DATE_EPOCH, there is no creation date to record
+    assert!(!String::from_utf8_lossy(&first).contains("/CreationDate"));
+}
+
+#[test]
+fn test_reproducible_today_needs_source_date_epoch() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("report.md");
+    fs::write(&input, DOCUMENT).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_md2pdf"))
+        .arg(&input)
+        .arg("--reproducible")
+        .env_remove("SOURCE_DATE_EPOCH")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("set SOURCE_DATE_EPOCH"));
+}
+
+#[test]
+fn test_source_date_epoch_pins_today() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("report.md");
+    fs::write(&input, DOCUMENT).unwrap();
+    let output = dir.path().join("report.pdf");
+    let typst = dir.path().join("report.typ");
+
+    // 2023-11-14 and 2020-09-13 (UTC)
+    let epochs = ["1700000000", "1600000000"];
+
+    let first = md2pdf(&input, &output, Some(epochs[0]), &[]);
+    thread::sleep(Duration::from_millis(1100));
+    let second = md2pdf(&input, &output, Some(epochs[0]), &[]);
+    assert_eq!(first, second);
+
+    let other = md2pdf(&input, &output, Some(epochs[1]), &[]);
+    assert_ne!(first, other);
+
+    let source = md2pdf(&input, &typst, Some(epochs[0]), &["--emit", "typst"]);
+    assert!(String::from_utf8_lossy(&source).contains("[2023-11-14]"));
+    let source = md2pdf(&input, &typst, Some(epochs[1]), &["--emit", "typst"]);
+    assert!(String::from_utf8_lossy(&source).contains("[2020-09-13]"));
+}
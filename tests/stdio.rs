@@ -0,0 +1,38 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn run_with_stdin(args: &[&str], input: &str) -> std::process::Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_md2pdf"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The process may exit before reading stdin, closing the pipe
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_stdin_to_stdout() {
+    let output = run_with_stdin(&["-", "--emit", "typst"], "# From a pipe\n");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("= From a pipe"));
+}
+
+#[test]
+fn test_stdin_pdf_to_stdout() {
+    let output = run_with_stdin(&["-", "-o", "-"], "# From a pipe\n");
+    assert!(output.status.success());
+    assert!(output.stdout.starts_with(b"%PDF-"));
+    // The whole document, with nothing printed after it
+    let end = String::from_utf8_lossy(&output.stdout[output.stdout.len() - 6..]).into_owned();
+    assert!(end.trim_end().ends_with("%%EOF"), "{end:?}");
+}
+
+#[test]
+fn test_stdin_cannot_emit_typst_and_output_together() {
+    let output = run_with_stdin(&["-", "--emit", "typst,output"], "# Title\n");
+    assert!(!output.status.success());
+}
@@ -0,0 +1,153 @@
+use md2pdf_rs::{convert_to_typst, Config};
+use std::fs;
+use std::process::Command;
+
+const DOCUMENT: &str = "---\ntheme: academic\nstyle:\n  font_size: 10pt\n  margin: 2cm\n  heading_numbering: \"1.a\"\n---\n\n# Intro\n";
+
+#[test]
+fn test_frontmatter_theme_and_style_apply_under_config() {
+    let typst = convert_to_typst(DOCUMENT, &Config::default()).unwrap().output;
+    assert!(typst.contains("#set page(paper: \"a4\", margin: 2cm)"));
+    assert!(typst.contains("#set text(size: 10pt)"));
+    assert!(typst.contains("#set heading(numbering: \"1.a\")"));
+    // Academic rather than default code blocks
+    assert!(typst.contains("font: \"Menlo\""));
+
+    let config = Config {
+        theme: Some("github".to_string()),
+        style: serde_yaml::from_str("font_size: 9pt").unwrap(),
+        ..Config::default()
+    };
+    let typst = convert_to_typst(DOCUMENT, &config).unwrap().output;
+    assert!(typst.contains("#set text(font: \"Inter\", size: 9pt)"));
+    assert!(typst.contains("margin: 2cm"));
+    assert!(!typst.contains("Menlo"));
+}
+
+#[test]
+fn test_frontmatter_theme_path_resolves_against_the_document() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("docs")).unwrap();
+    fs::write(
+        dir.path().join("docs/brand.toml"),
+        "extends = \"github\"\n[text]\nsize = \"13pt\"\n",
+    )
+    .unwrap();
+    let input = dir.path().join("docs/doc.md");
+    fs::write(&input, "---\ntheme: ./brand.toml\n---\n# Intro\n").unwrap();
+
+    // Run from elsewhere, so that only the document's directory has the theme
+    let status = Command::new(env!("CARGO_BIN_EXE_md2pdf"))
+        .current_dir(dir.path())
+        .arg("docs/doc.md")
+        .args(["--emit", "typst"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let typst = fs::read_to_string(dir.path().join("docs/doc.typ")).unwrap();
+    assert!(typst.contains("size: 13pt"), "{typst}");
+}
+
+#[test]
+fn test_command_line_overrides_frontmatter() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("doc.md");
+    fs::write(&input, DOCUMENT).unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_md2pdf"))
+        .arg(&input)
+        .args(["--emit", "typst", "--theme", "github", "--style", "font_size=9pt"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let typst = fs::read_to_string(dir.path().join("doc.typ")).unwrap();
+    assert!(typst.contains("#set text(font: \"Inter\", size: 9pt)"));
+    assert!(typst.contains("margin: 2cm"));
+    assert!(!typst.contains("Menlo"));
+}
+
+#[test]
+fn test_unknown_style_key_is_an_error() {
+    let markdown = "---\nstyle:\n  fontsize: 10pt\n---\n";
+    let err = convert_to_typst(markdown, &Config::default()).unwrap_err();
+    assert!(err.to_string().contains("unknown field `fontsize`"), "{err}");
+}
+
+#[test]
+fn test_paper_orientation_and_side_margins() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("doc.md");
+    fs::write(&input, "---\nstyle:\n  margin:\n    left: 3cm\n---\n# Intro\n").unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_md2pdf"))
+        .arg(&input)
+        .args(["--emit", "typst", "--paper", "a5", "--landscape"])
+        .args(["--style", "margin.top=1in"])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let typst = fs::read_to_string(dir.path().join("doc.typ")).unwrap();
+    assert!(typst.contains("#set page(paper: \"a5\", flipped: true, margin: (left: 3cm, top: 1in"));
+}
+
+#[test]
+fn test_invalid_paper_is_rejected() {
+    let output = Command::new(env!("CARGO_BIN_EXE_md2pdf"))
+        .args(["doc.md", "--paper", "a44"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("did you mean `a4`?"));
+
+    let config = Config {
+        paper_size: "b99".to_string(),
+        ..Config::default()
+    };
+    assert!(convert_to_typst("# Intro\n", &config).is_err());
+}
+
+#[test]
+fn test_no_raw_typst_refuses_typst_in_the_frontmatter() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("x.typ"), "#text(red)[INJECTED]\n").unwrap();
+    let config = Config {
+        raw_typst: false,
+        base_dir: Some(dir.path().to_path_buf()),
+        ..Config::default()
+    };
+
+    for (frontmatter, message) in [
+        (
+            "style:\n  font_size: \"10pt)\\n#text(red)[INJECTED]\\n#set text(size: 10pt\"",
+            "invalid font_size",
+        ),
+        (
+            "style:\n  link_color: \"red)\\n#text(red)[INJECTED]\\n#set text(fill: red\"",
+            "invalid link_color",
+        ),
+        ("theme: ./x.typ", "theme file `./x.typ`"),
+        ("title: T\ncover: ./x.typ", "cover file `./x.typ`"),
+        (
+            "title: T\ncover: classic\ncover_background: \"red, text(red)[INJECTED]\"",
+            "invalid cover_background",
+        ),
+    ] {
+        let markdown = format!("---\n{frontmatter}\n---\n# Intro\n");
+        let err = convert_to_typst(&markdown, &config).unwrap_err();
+        assert!(err.to_string().contains(message), "{err}");
+    }
+
+    let markdown = "---\ntitle: T\ncover: classic\ncover_background: \"#eeeeee\"\nstyle:\n  font_size: 10pt\n  text_color: \"#333\"\n  margin:\n    x: 2cm\n---\n# Intro\n";
+    let typst = convert_to_typst(markdown, &config).unwrap().output;
+    assert!(typst.contains("size: 10pt"));
+    assert!(typst.contains("background: rgb(\"#eeeeee\")"));
+    assert!(!typst.contains("INJECTED"));
+
+    // With raw Typst on, the same values are Typst expressions as before
+    let markdown = "---\nstyle:\n  font_size: 10pt + 1pt\n---\n# Intro\n";
+    let typst = convert_to_typst(markdown, &Config::default()).unwrap().output;
+    assert!(typst.contains("size: 10pt + 1pt"));
+}